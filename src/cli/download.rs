@@ -0,0 +1,156 @@
+use mapito::{
+    client::{Downloader, FileDownload},
+    config::Configuration,
+    mc_info::MVDescriptor,
+    mrapi::{
+        client::ModrinthClient,
+        defines::{ApiFile, Version},
+    },
+    pack::{file_hash, split_version},
+    util::{error::ApiError, progress::Progress},
+};
+
+use crate::cli::input::confirm_input;
+
+/// Download a mod for the configured version, asking whether its dependencies should be downloaded too.
+///
+/// `slug@version` downloads the given version id or number instead.
+pub fn download_mod(
+    client: &ModrinthClient,
+    config: &Configuration,
+    dl_id: &str,
+) -> Result<(), ApiError> {
+    let version_desc = MVDescriptor {
+        mc_ver: config.mc_ver.clone(),
+        version_types: vec![config.release_type.clone()],
+        loader: config.loader,
+    };
+    let dl_version: Version = match split_version(dl_id) {
+        (slug, Some(version)) => {
+            let dl_version = client.get_project_version_by_number(slug, version)?;
+            if !version_desc.mc_ver.is_latest()
+                && !dl_version.game_versions.contains(&version_desc.mc_ver)
+                || !dl_version.supports_loader(&version_desc.loader)
+            {
+                println!(
+                    "Warning: {} is not made for {} {}",
+                    dl_version.version_number, version_desc.mc_ver, version_desc.loader
+                );
+            }
+            dl_version
+        }
+        (slug, None) => client.get_project_version(slug, &version_desc)?,
+    };
+
+    let mut dependencies: Vec<Version> = Vec::new();
+    for dependency in &dl_version.dependencies {
+        let dep_ver = match (&dependency.project_id, &dependency.version_id) {
+            (Some(project_id), _) => client.get_project_version(project_id, &version_desc),
+            (None, Some(version_id)) => client.get_version(version_id),
+            (None, None) => continue,
+        };
+        let dep_ver = match dep_ver {
+            Ok(v) => v,
+            Err(e) if e.is_not_found() => {
+                println!("{}", e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        dependencies.push(dep_ver);
+    }
+
+    let mut dl_size = (dl_version.primary_file()?.size as f64 / 1048576_f64).to_string();
+    dl_size.truncate(6);
+    println!(
+        "Downloading: {}, {}\ntype: {}, downloads: {}, loader: {:?}\nsize: {} MiB",
+        dl_version.name,
+        dl_version.version_number,
+        dl_version.version_type,
+        dl_version.downloads,
+        dl_version.loaders,
+        dl_size
+    );
+
+    if confirm_input() {
+        println!("Downloading to {}", &config.download_path);
+        download_version(client, config, &dl_version)?;
+    } else {
+        println!("Aborting");
+        return Ok(());
+    }
+
+    let extra_files = dl_version.extra_files();
+    if !extra_files.is_empty() {
+        println!("The version has these extra files:");
+        for file in &extra_files {
+            println!(
+                " {} ({}), {:.2} MiB",
+                file.filename,
+                file.file_type.as_deref().unwrap_or("additional file"),
+                file.size as f64 / 1048576_f64
+            );
+        }
+        println!("Download these too?");
+        if confirm_input() {
+            for file in extra_files {
+                download_file(client, config, file)?;
+            }
+        }
+    }
+
+    if !dependencies.is_empty() {
+        print!(
+            "Found the following dependencies:\n {}",
+            dependencies
+                .iter()
+                .map(|dep| dep.name.clone()
+                    + ", "
+                    + &(dep.primary_file().map_or(0, |file| file.size) as f64 / 1048576_f64)
+                        .to_string()
+                    + "MB\n")
+                .collect::<String>()
+        );
+        println!("Download these too?");
+        if confirm_input() {
+            for dep in dependencies {
+                println!("Downloading {}", dep.name);
+                download_version(client, config, &dep)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn download_version(
+    client: &ModrinthClient,
+    config: &Configuration,
+    version: &Version,
+) -> Result<(), ApiError> {
+    download_file(client, config, version.primary_file()?)
+}
+
+fn download_file(
+    client: &ModrinthClient,
+    config: &Configuration,
+    file: &ApiFile,
+) -> Result<(), ApiError> {
+    let sha512 = file_hash(file, "sha512");
+    if sha512.is_empty() {
+        return Err(ApiError::invalid_data(format!(
+            "No sha512 hash for '{}'",
+            file.filename
+        )));
+    }
+    let download = FileDownload {
+        path: config.download_path.clone() + "/" + &file.filename,
+        url: file.url.clone(),
+        sha512,
+        size: file.size,
+    };
+    client.http().download_file(
+        &download,
+        &config.quarantine_path,
+        &Progress::new(file.size, 1),
+    )
+}
@@ -1,6 +1,6 @@
 use std::io;
 
-use crate::{config::Configuration, pack::pack::{Pack, PackAction}};
+use mapito::{config::Configuration, pack::pack::{Pack, PackAction}, say, util::error::ApiError};
 
 pub fn confirm_input() -> bool {
     println!("proceed? [Y,n]");
@@ -27,8 +27,8 @@ pub fn read_line_to_string() -> String {
     buf.to_string().replace("\n", "").replace("\"", "")
 }
 
-pub fn query_pack(action: PackAction, config: &Configuration) -> Pack {
-    println!("Please enter the name of the Pack you want to {}", action.to_string());
+pub fn query_pack(action: PackAction, config: &Configuration) -> Result<Pack, ApiError> {
+    say!("Please enter the name of the Pack you want to {}", action);
     let name = read_line_to_string();
     Pack::open(&name, config)
 }
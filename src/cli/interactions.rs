@@ -1,13 +1,16 @@
 use std::{fmt::Display, str::FromStr};
 
-use reqwest::blocking::Client;
+use colored::Colorize;
 
-use crate::{
-    cli::input::read_line_to_string, config::Configuration, mrapi::interactions::search_package,
+use mapito::{
+    mrapi::{client::ModrinthClient, defines::Project},
+    util::error::ApiError,
 };
 
+use crate::cli::input::read_line_to_string;
+
 /// repeats prompt to search for mods and returns a vector of the slugs of all chosen mods
-pub fn search_mods(client: &Client, config: &Configuration) -> Vec<String> {
+pub fn search_mods(client: &ModrinthClient) -> Vec<String> {
     println!("Search for mods and add them to the pack.");
 
     let mut mods: Vec<String> = Vec::new();
@@ -19,7 +22,7 @@ pub fn search_mods(client: &Client, config: &Configuration) -> Vec<String> {
                 break;
             }
         };
-        match query_reader(&query, client, config) {
+        match query_reader(&query, client) {
             Some(slug) => mods.push(slug),
             None => println!("No mods Found"),
         }
@@ -34,13 +37,19 @@ pub fn prompt_for<T: FromStr>(prompt: &str) -> Option<T>
 where
     T::Err: Display,
 {
+    prompt_with(prompt, T::from_str)
+}
+
+/// Prompt user for a value that is parsed and checked by `parse`, e.g. against the modrinth tags.
+/// Asks again until the input is valid, if user enters 'q' returns None
+pub fn prompt_with<T, E: Display>(prompt: &str, parse: impl Fn(&str) -> Result<T, E>) -> Option<T> {
     loop {
         println!("{} or press 'q' to quit:", prompt);
         let result = read_line_to_string();
         if result == "q" {
             break;
         }
-        let obj = match T::from_str(&result) {
+        let obj = match parse(&result) {
             Ok(obj) => obj,
             Err(e) => {
                 println!("parsing input failed");
@@ -71,12 +80,12 @@ where
     ret
 }
 
-fn query_reader(query: &String, client: &Client, config: &Configuration) -> Option<String> {
+fn query_reader(query: &str, client: &ModrinthClient) -> Option<String> {
     let mut offset = 0;
     loop {
-        let slugs = search_package(client, query, config.staging, None, Some(offset));
+        let slugs = search_package(client, query, None, Some(offset));
         match slugs {
-            Some(sl) => {
+            Ok(sl) => {
                 println!(
                     "Select mod from 0 to {} or 'p'/'n' to change page, enter 'q' to quit.",
                     sl.len() - 1
@@ -107,10 +116,86 @@ fn query_reader(query: &String, client: &Client, config: &Configuration) -> Opti
                     }
                 }
             }
-            None => {
+            Err(e) => {
+                println!("{}", e);
+                println!("Query failed.");
                 break;
             }
         }
     }
     return None;
 }
+
+/// print one page of search results, returns the slugs of the shown projects
+pub fn search_package(
+    client: &ModrinthClient,
+    query: &str,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Vec<String>, ApiError> {
+    let query_response = client.search(query, limit.unwrap_or(10), offset.unwrap_or(0))?;
+
+    let mut slugs: Vec<String> = Vec::new();
+    for (counter, hit) in query_response.hits.iter().enumerate() {
+        let latest = hit["versions"]
+            .as_array()
+            .and_then(|versions| versions.last())
+            .and_then(|version| version.as_str())
+            .unwrap_or("none");
+        println!(
+            "{counter} {}|{},{}, MC-{}, by: {}, downloads: {}\n{}\n",
+            hit["slug"].to_string().replace("\"", "").green(),
+            hit["title"].to_string().replace("\"", ""),
+            hit["project_type"].to_string().replace("\"", ""),
+            latest,
+            hit["author"].to_string().replace("\"", ""),
+            hit["downloads"].to_string().replace("\"", ""),
+            hit["description"]
+                .to_string()
+                .replace("\"", "")
+                .bright_black(),
+        );
+        slugs.push(hit["slug"].to_string().replace("\"", ""));
+    }
+
+    Ok(slugs)
+}
+
+/// print a summary of a project and its members
+pub fn print_project_info(client: &ModrinthClient, project_slug: &str) -> Result<(), ApiError> {
+    let project: Project = client.get_project(project_slug)?;
+    let members = client.get_members(project_slug)?;
+    println!(
+        "Project: {}, latest-{}, {}\n {}\n\n Released: {}\n Last Updated: {} \n \
+        loaders: {}\n supported versions: \n{} license: {}\n source: {}\n members:\n{}",
+        project.title,
+        // drafts have no game versions yet
+        project.game_versions.last().map_or("none".to_string(), |v| v.to_string()),
+        project.project_type.green(),
+        project.description,
+        project.published.yellow(),
+        project.updated.yellow(),
+        project
+            .loaders
+            .iter()
+            .map(|e| e.to_string() + ",")
+            .collect::<String>(),
+        project
+            .game_versions
+            .iter()
+            .rev()
+            .take(10)
+            .map(|e| "  ".to_string() + &e.to_string() + "\n")
+            .collect::<String>(),
+        project.license.name,
+        match project.source_url {
+            Some(v) => v.bright_blue(),
+            None => "none".to_string().red(),
+        },
+        members
+            .iter()
+            .map(|mem| "  ".to_string() + &mem.user.username.clone() + ", " + &mem.role + "\n")
+            .collect::<String>(),
+    );
+    Ok(())
+}
@@ -1,2 +1,6 @@
+pub mod download;
 pub mod input;
 pub mod interactions;
+pub mod pack;
+pub mod publish;
+pub mod validate;
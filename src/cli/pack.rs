@@ -0,0 +1,241 @@
+use mapito::{
+    config::Configuration,
+    mc_info::{MCVersion, MVDescriptor, LOADER, VT},
+    mrapi::{client::ModrinthClient, tags::Tags},
+    pack::{
+        create_pack,
+        pack::{Pack, PackAction},
+        update_pack,
+    },
+    util::error::ApiError,
+};
+
+use crate::cli::{
+    input::{confirm_input, query_pack, read_line_to_string},
+    interactions::{prompt_for, prompt_multiple, prompt_with, search_mods},
+};
+
+/// Arguments of the pack commands.
+#[derive(Debug, Default)]
+pub struct PackArgs {
+    pub action: Option<PackAction>,
+    /// write the changelog of an update to this file, as markdown
+    pub changelog_path: Option<String>,
+    /// only print what install and update would change
+    pub dry_run: bool,
+    /// print the plan of a dry run as JSON instead of text
+    pub json: bool,
+}
+
+/// Interactively create a new pack
+pub fn pack_creation_loop(
+    client: &ModrinthClient,
+    config: &Configuration,
+    tags: &Tags,
+) -> Result<(), ApiError> {
+    let mut version_desc = MVDescriptor {
+        mc_ver: MCVersion::new(),
+        version_types: vec![VT::RELEASE],
+        loader: LOADER::FABRIC,
+    };
+
+    let abort_msg = "Aborting pack creation.";
+
+    println!("Please enter the Name of the new Pack:");
+    let name = read_line_to_string();
+    version_desc.mc_ver =
+        match prompt_with("Please enter the Minecraft version of this pack", |s| {
+            tags.parse_mc_version(s)
+        }) {
+            Some(ver) => ver,
+            None => {
+                println!("{}", abort_msg);
+                return Ok(());
+            }
+        };
+    version_desc.loader = match prompt_with("Please enter what loader you want to use", |s| {
+        tags.parse_loader(s)
+    }) {
+        Some(loader) => loader,
+        None => {
+            println!("{}", abort_msg);
+            return Ok(());
+        }
+    };
+    version_desc.version_types = prompt_multiple("Please enter one of 'release' 'beta' 'alpha'");
+    println!("Please confirm your input:\n Pack Name: {name}\n Minecraft version: {}\n Mod Loader: {}\n version types: {}",
+        version_desc.mc_ver,
+        version_desc.loader,
+        version_desc.version_types.iter().map(|vt| vt.to_string() + " ").collect::<String>());
+    if !confirm_input() {
+        println!("Aborting pack Creation");
+        return Ok(());
+    }
+    println!(
+        "Now you can search for mods and add them to the pack, you can finish by entering 'q'"
+    );
+    let mods: Vec<String> = search_mods(client);
+
+    create_pack(client, name, version_desc, &mods, config)
+}
+
+/// Interactively change the name, version info or mods of a pack
+pub fn pack_modification_loop(
+    client: &ModrinthClient,
+    config: &Configuration,
+    tags: &Tags,
+) -> Result<(), ApiError> {
+    let mut pack = query_pack(PackAction::MODIFY, config)?;
+    loop {
+        println!("{}", pack);
+        match prompt_for::<char>("choose a category to modify:\n0 - Name\n1 - Version Info\n\tMinecraft Version\n\tVersion Types\n\tLoader\n2 - Mods\n") {
+            Some('0') => {
+                pack.remove(config)?;
+                match prompt_for::<String>("Enter a new name for the Pack.") {
+                    Some(name) => pack.name = name,
+                    None => println!("Name not changed."),
+                };
+                return pack.save(config);
+            }
+            Some('1') => {
+                let true_name = pack.name.clone();
+                pack.name = pack.name + "_tmp";
+                loop {
+                    println!("What do you want to change?");
+                    println!("  0 - Minecraft Version: {}", pack.version_info.mc_ver);
+                    println!(
+                        "  1 - Version Types: {}",
+                        pack.version_info
+                            .version_types
+                            .iter()
+                            .map(|vt| vt.to_string() + " ")
+                            .collect::<String>()
+                    );
+                    println!("  2 - Loader: {}", pack.version_info.loader);
+                    match prompt_for::<char>("") {
+                        Some('0') => {
+                            match prompt_with("enter a new Minecraft version for the Pack.", |s| tags.parse_mc_version(s)) {
+                                Some(ver) => {pack.version_info.mc_ver = ver},
+                                None => {
+                                    println!("Version not changed.");
+                                },
+                            };
+                        }
+                        Some('1') => {
+                            println!("enter new version types for the Pack.");
+                            pack.version_info.version_types = prompt_multiple("Enter new version types for the Pack.");
+                        }
+                        Some('2') => {
+                            match prompt_with("Please enter the loader you want to change to", |s| tags.parse_loader(s)) {
+                                Some(loader) => pack.version_info.loader = loader,
+                                None => println!("Loader not changed."),
+                            };
+                        }
+                        None => break,
+                        _ => println!("unexpected input"),
+                    }
+                }
+                pack.save(config)?;
+                println!("updating mods.");
+                match update_pack(client, pack.name.clone(), config) {
+                    Ok(_) => {
+                        pack = Pack::open(&pack.name, config)?;
+                        pack.remove(config)?;
+                        pack.name = true_name;
+                        pack.save(config)?;
+                    }
+                    Err(e) => {
+                        eprintln!("Updating the mods failed, the pack was not changed: {e}");
+                        pack.remove(config)?;
+                        pack.name = true_name;
+                    }
+                };
+                pack = Pack::open(&pack.name, config)?;
+            }
+            Some('2') => loop {
+                pack.list_mods();
+                println!("Choose an Action:");
+                println!("  0 - add mods");
+                println!("  1 - remove a mod");
+                println!("  2 - pin a mod to a version");
+                println!("  3 - unpin a mod");
+                println!("  4 - choose extra files of a mod");
+                match prompt_for::<char>("") {
+                    Some('0') => {
+                        let mods = search_mods(client);
+                        for item in mods {
+                            if let Err(e) = pack.add_mod(&item, client) {
+                                eprintln!("Adding {item} failed: {e}");
+                            }
+                        }
+                        pack.save(config)?;
+                    }
+                    Some('1') => {
+                        println!("Enter which mod to remove:");
+                        pack.mods.remove(&read_line_to_string());
+                        pack.save(config)?;
+                        pack = Pack::open(&pack.name, config)?;
+                    }
+                    Some('2') => {
+                        println!("Enter which mod to pin:");
+                        let key = read_line_to_string();
+                        println!("Enter the version id or number:");
+                        let version = read_line_to_string();
+                        let pinned = client
+                            .get_project_version_by_number(&key, &version)
+                            .and_then(|v| pack.pin_mod(&key, &v));
+                        match pinned {
+                            Ok(()) => pack.save(config)?,
+                            Err(e) => eprintln!("Pinning {key} failed: {e}"),
+                        }
+                    }
+                    Some('3') => {
+                        println!("Enter which mod to unpin:");
+                        let key = read_line_to_string();
+                        match pack.unpin_mod(&key) {
+                            Ok(()) => pack.save(config)?,
+                            Err(e) => eprintln!("Unpinning {key} failed: {e}"),
+                        }
+                    }
+                    Some('4') => {
+                        println!("Enter which mod to choose extra files for:");
+                        let key = read_line_to_string();
+                        if let Err(e) = extra_files_dialog(client, &mut pack, &key) {
+                            eprintln!("Choosing extra files for {key} failed: {e}");
+                        } else {
+                            pack.save(config)?;
+                        }
+                    }
+                    None => break,
+                    _ => println!("unexpected input"),
+                }
+            },
+            None => return Ok(()),
+            _ => println!("unexpected input"),
+        }
+    }
+}
+
+/// let the user pick which extra files of a mod are installed with it
+fn extra_files_dialog(client: &ModrinthClient, pack: &mut Pack, key: &str) -> Result<(), ApiError> {
+    let version = pack.mod_version(key, client)?;
+    let extra_files = version.extra_files();
+    if extra_files.is_empty() {
+        println!("{} has no extra files", version.name);
+        return Ok(());
+    }
+    for (i, file) in extra_files.iter().enumerate() {
+        println!(
+            "  {i} - {} ({})",
+            file.filename,
+            file.file_type.as_deref().unwrap_or("additional file")
+        );
+    }
+    let file_names: Vec<String> = prompt_multiple::<usize>("Enter the number of a file to install")
+        .into_iter()
+        .filter_map(|i| extra_files.get(i).map(|file| file.filename.clone()))
+        .collect();
+    pack.set_extra_files(key, &version, &file_names)?;
+    println!("{key} installs {} extra files", file_names.len());
+    Ok(())
+}
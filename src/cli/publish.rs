@@ -0,0 +1,231 @@
+use std::{fs::read_to_string, path::Path, str::FromStr};
+
+use mapito::{
+    config::Configuration,
+    mc_info::{MCVersion, LOADER, VT},
+    mrapi::{
+        client::ModrinthClient,
+        defines::{Dependency, NewVersion, Version},
+        tags::Tags,
+    },
+    util::error::ApiError,
+};
+
+/// name of the multipart field the uploaded file is sent in
+const FILE_PART: &str = "file";
+
+/// Arguments of the publish command, lists are comma separated.
+#[derive(Debug, Default)]
+pub struct PublishArgs {
+    pub file: String,
+    pub project: String,
+    pub version_number: String,
+    /// release, beta or alpha, release if empty
+    pub version_type: String,
+    pub name: String,
+    pub changelog: String,
+    pub game_versions: String,
+    pub loaders: String,
+    pub dependencies: String,
+}
+
+/// Publish the jar given in `args` as a new version of a project.
+///
+/// Game versions and loaders default to the ones in the config, the version
+/// type defaults to release. Dependencies are given as `project[:type]`, the
+/// type defaults to required. Game versions and loaders are checked against
+/// the tags.
+pub fn publish(
+    client: &ModrinthClient,
+    config: &Configuration,
+    args: &PublishArgs,
+    tags: &Tags,
+) -> Result<Version, ApiError> {
+    if args.project.is_empty() || args.version_number.is_empty() {
+        return Err(ApiError::invalid_data(
+            "Publishing needs a project and a version number",
+        ));
+    }
+    if !Path::new(&args.file).is_file() {
+        return Err(ApiError::invalid_data(format!(
+            "'{}' is not a file",
+            args.file
+        )));
+    }
+
+    let game_versions: Vec<MCVersion> = if args.game_versions.is_empty() {
+        vec![config.mc_ver.clone()]
+    } else {
+        parse_list(&args.game_versions, |s| tags.parse_mc_version(s))?
+    };
+    if game_versions.iter().any(|ver| ver.is_latest()) {
+        return Err(ApiError::invalid_data(
+            "Game versions have to be given explicitly, not as 'latest'",
+        ));
+    }
+    let loaders: Vec<LOADER> = if args.loaders.is_empty() {
+        vec![config.loader]
+    } else {
+        parse_list(&args.loaders, |s| tags.parse_loader(s))?
+    };
+
+    let version_type = if args.version_type.is_empty() {
+        VT::RELEASE
+    } else {
+        VT::from_str(args.version_type.trim()).map_err(ApiError::invalid_data)?
+    };
+
+    let changelog = if args.changelog.is_empty() {
+        None
+    } else if Path::new(&args.changelog).is_file() {
+        Some(read_to_string(&args.changelog).map_err(|e| ApiError::from(e).at(&args.changelog))?)
+    } else {
+        Some(args.changelog.clone())
+    };
+
+    let project_id = client.get_project(&args.project)?.id;
+
+    let mut dependencies: Vec<Dependency> = Vec::new();
+    let dep_args: Vec<(String, String)> = args
+        .dependencies
+        .split(',')
+        .filter(|dep| !dep.trim().is_empty())
+        .map(|dep| match dep.trim().split_once(':') {
+            Some((project, kind)) => (project.to_string(), kind.to_string()),
+            None => (dep.trim().to_string(), "required".to_string()),
+        })
+        .collect();
+    let dep_slugs: Vec<String> = dep_args.iter().map(|(slug, _)| slug.clone()).collect();
+    let dep_projects = client.get_projects(&dep_slugs)?;
+    for (slug, dependency_type) in dep_args {
+        let project = match dep_projects.iter().find(|p| p.slug == slug || p.id == slug) {
+            Some(p) => p,
+            None => return Err(ApiError::not_found().at(slug)),
+        };
+        dependencies.push(Dependency {
+            project_id: Some(project.id.clone()),
+            version_id: None,
+            dependency_type,
+        });
+    }
+
+    let new_version = NewVersion {
+        project_id,
+        name: if args.name.is_empty() {
+            args.version_number.clone()
+        } else {
+            args.name.clone()
+        },
+        version_number: args.version_number.clone(),
+        changelog,
+        dependencies,
+        game_versions,
+        version_type,
+        loaders,
+        featured: false,
+        file_parts: vec![FILE_PART.to_string()],
+        primary_file: FILE_PART.to_string(),
+    };
+
+    println!(
+        "Publishing {} {} for {}",
+        args.project,
+        new_version.version_number,
+        new_version
+            .game_versions
+            .iter()
+            .map(|ver| ver.to_string() + " ")
+            .collect::<String>()
+    );
+    client.create_version(&new_version, &args.file)
+}
+
+fn parse_list<T>(
+    list: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Vec<T>, ApiError> {
+    list.split(',')
+        .map(|item| parse(item.trim()).map_err(ApiError::invalid_data))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::testing::{serve, Response, TempDir};
+
+    fn respond(path: &str) -> Value {
+        match path {
+            "/project/mymod" => json!({
+                "id": "AABBCCDD", "slug": "mymod", "project_type": "mod", "team": "t",
+                "title": "My Mod", "description": "", "published": "", "updated": "",
+                "license": {"id": "MIT", "name": "MIT", "url": null}, "downloads": 0,
+                "game_versions": ["1.21.5"], "categories": [], "loaders": ["fabric"],
+                "source_url": null
+            }),
+            _ => json!({
+                "id": "NEWID", "project_id": "AABBCCDD", "game_versions": ["1.21.5"],
+                "loaders": ["fabric"], "name": "1.0.0", "version_number": "1.0.0",
+                "downloads": 0, "version_type": "beta", "files": [], "dependencies": []
+            }),
+        }
+    }
+
+    #[test]
+    fn publish_sends_the_version_type() {
+        let (url, requests) = serve(2, |request| Response::json(respond(&request.path)));
+        let client = ModrinthClient::new(&url);
+        let dir = TempDir::new("publish-test");
+        let jar = dir.join("mymod.jar");
+        write(&jar, b"jar").unwrap();
+        let config = Configuration {
+            mc_ver: MCVersion::from_str("1.21.5").unwrap(),
+            ..Default::default()
+        };
+        let args = PublishArgs {
+            file: jar.to_string_lossy().to_string(),
+            project: "mymod".to_string(),
+            version_number: "1.0.0".to_string(),
+            version_type: "beta".to_string(),
+            ..Default::default()
+        };
+
+        let version = publish(&client, &config, &args, &Tags::default()).unwrap();
+
+        assert_eq!(version.id, "NEWID");
+        assert_eq!(requests.recv().unwrap().path, "/project/mymod");
+        let request = requests.recv().unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/version")
+        );
+        assert!(request.body.contains(r#""version_type":"beta""#));
+        assert!(request.body.contains(r#""project_id":"AABBCCDD""#));
+    }
+
+    #[test]
+    fn publish_rejects_an_unknown_version_type() {
+        let dir = TempDir::new("publish-test-invalid");
+        let jar = dir.join("mymod.jar");
+        write(&jar, b"jar").unwrap();
+        let config = Configuration {
+            mc_ver: MCVersion::from_str("1.21.5").unwrap(),
+            ..Default::default()
+        };
+        let args = PublishArgs {
+            file: jar.to_string_lossy().to_string(),
+            project: "mymod".to_string(),
+            version_number: "1.0.0".to_string(),
+            version_type: "stable".to_string(),
+            ..Default::default()
+        };
+
+        let client = ModrinthClient::new("http://127.0.0.1:9");
+        let err = publish(&client, &config, &args, &Tags::default()).unwrap_err();
+        assert!(err.to_string().contains("invalid version type"));
+    }
+}
@@ -0,0 +1,41 @@
+use mapito::{
+    config::Configuration,
+    mrapi::{client::ModrinthClient, tags::Tags},
+    util::error::ApiError,
+};
+
+/// fetch the tags to check user input against, without them every input is accepted
+pub fn load_tags(client: &ModrinthClient) -> Tags {
+    match Tags::fetch(client) {
+        Ok(tags) => tags,
+        Err(e) => {
+            eprintln!("Could not fetch versions and loaders, input is not checked: {e}");
+            Tags::default()
+        }
+    }
+}
+
+/// Check the Minecraft version and loader of the config, including the ones set with `-v`/`-l`.
+///
+/// The loader flag and a loader of the config file that did not parse are
+/// parsed here rather than when they are read, so that a typo gets a
+/// suggestion. The flag wins over the config file.
+pub fn validate_config(
+    config: &mut Configuration,
+    tags: &Tags,
+    loader_arg: &str,
+) -> Result<(), ApiError> {
+    if !loader_arg.is_empty() {
+        config.loader = tags
+            .parse_loader(loader_arg)
+            .map_err(ApiError::invalid_data)?;
+    } else if let Some(loader) = config.unchecked_loader.take() {
+        config.loader = tags
+            .parse_loader(&loader)
+            .map_err(|e| ApiError::invalid_data(e).at("loader in config.toml"))?;
+    }
+    tags.check_loader(&config.loader)
+        .map_err(ApiError::invalid_data)?;
+    tags.check_mc_version(&config.mc_ver)
+        .map_err(ApiError::invalid_data)
+}
@@ -1,26 +1,397 @@
-use core::panic;
+use std::{
+    fs::{copy, create_dir_all, metadata, read_to_string, remove_file, rename, write, File, OpenOptions},
+    io::{copy as copy_io, Read, Write},
+    path::Path,
+};
 
-use reqwest::{blocking::Client, Result};
+use reqwest::{
+    blocking::Client,
+    header::{ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    StatusCode,
+};
 use sha2::{Digest, Sha512};
 
+use crate::util::{
+    error::{ApiError, ApiErrorKind},
+    progress::{FileProgress, Progress},
+};
+
+/// size of the chunks a download is read in
+const CHUNK_SIZE: usize = 64 * 1024;
+/// suffix of the temporary file a download is written to
+pub const PART_SUFFIX: &str = ".part";
+/// suffix of the file next to a `.part` file holding the ETag or Last-Modified date of the download
+const VALIDATOR_SUFFIX: &str = ".part.validator";
+/// how often an interrupted download is resumed before giving up
+const RESUME_ATTEMPTS: u32 = 3;
+
+/// A file to download, together with the checks it has to pass.
+#[derive(Debug, Clone)]
+pub struct FileDownload {
+    pub path: String,
+    pub url: String,
+    pub sha512: String,
+    /// the size in bytes, 0 if unknown, it is not checked then
+    pub size: u64,
+}
+
 pub trait Downloader {
-    fn download_file(&self, path: &str, url: &str, hash: &str) -> Result<()>;
+    /// Download a file and check its sha512 hash and size.
+    ///
+    /// The body is streamed into a `.part` file next to `path`, which is
+    /// only renamed to `path` once both checks passed. A file failing them is
+    /// moved into the `quarantine` directory, so it can be inspected.
+    ///
+    /// Interrupted downloads keep their `.part` file and are resumed with a
+    /// range request, if the server still has the same file. The hash is
+    /// always checked over the complete file.
+    fn download_file(
+        &self,
+        file: &FileDownload,
+        quarantine: &str,
+        progress: &Progress,
+    ) -> Result<(), ApiError>;
 }
 impl Downloader for Client {
-    fn download_file(&self, path: &str, url: &str, hash: &str) -> Result<()> {
-        let body = self.get(url).send().unwrap().bytes().unwrap();
+    fn download_file(
+        &self,
+        file: &FileDownload,
+        quarantine: &str,
+        progress: &Progress,
+    ) -> Result<(), ApiError> {
+        let part_path = file.path.clone() + PART_SUFFIX;
+        let validator_path = file.path.clone() + VALIDATOR_SUFFIX;
+        let file_progress = progress.start_file(file_name(&file.path), file.size);
+        let mut attempt = 0;
+        let result = loop {
+            match fetch_part(self, file, &part_path, &validator_path, &file_progress) {
+                Err(e) if is_interrupted(&e) && attempt < RESUME_ATTEMPTS => attempt += 1,
+                result => break result,
+            }
+        };
+        drop(file_progress);
 
-        println!("Checking data integrity.");
+        // the part file is kept, so the next attempt can resume it
+        let (dl_hash, dl_size) = result?;
+        let _ = remove_file(&validator_path);
+        if file.size != 0 && dl_size != file.size {
+            return Err(ApiError::from(ApiErrorKind::SizeMismatch {
+                expected: file.size,
+                actual: dl_size,
+                quarantined: quarantine_file(&part_path, &file.path, quarantine),
+            })
+            .at(&file.url));
+        }
+        if dl_hash != file.sha512 {
+            return Err(ApiError::from(ApiErrorKind::HashMismatch {
+                expected: file.sha512.clone(),
+                actual: dl_hash,
+                quarantined: quarantine_file(&part_path, &file.path, quarantine),
+            })
+            .at(&file.url));
+        }
+        rename(&part_path, &file.path).map_err(|e| ApiError::from(e).at(&file.path))
+    }
+}
 
-        let dl_hash = Sha512::digest(&body);
+/// Download the file into `part_path`, resuming what is already there.
+///
+/// Returns the sha512 hash and size of the complete part file.
+fn fetch_part(
+    client: &Client,
+    file: &FileDownload,
+    part_path: &str,
+    validator_path: &str,
+    progress: &FileProgress,
+) -> Result<(String, u64), ApiError> {
+    let existing = metadata(part_path).map(|m| m.len()).unwrap_or(0);
+    let validator = read_to_string(validator_path).unwrap_or_default();
+    let mut request = client.get(&file.url);
+    // If-Range makes the server send the whole file if it changed since the part was written
+    if existing > 0 && !validator.is_empty() {
+        request = request
+            .header(RANGE, format!("bytes={existing}-"))
+            .header(IF_RANGE, validator.trim());
+    }
+    let mut response = request.send()?;
+    let status = response.status();
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        // the part is as long as the file or longer, it cannot be resumed
+        let _ = remove_file(part_path);
+        let _ = remove_file(validator_path);
+        return fetch_part(client, file, part_path, validator_path, progress);
+    }
+    if !status.is_success() {
+        return Err(ApiError::http(status, String::new()).at(&file.url));
+    }
 
-        let hx_hash = base16ct::lower::encode_string(&dl_hash);
-        if hx_hash == hash {
-            println!("Integrity check passed.");
-            let _ = std::fs::write(path, &body).unwrap();
-        } else {
-            panic!("Downloaded Data does not match provided Hash!")
+    let mut hasher = Sha512::new();
+    let mut part = if status == StatusCode::PARTIAL_CONTENT {
+        let mut part = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(part_path)
+            .map_err(|e| ApiError::from(e).at(part_path))?;
+        copy_io(&mut part, &mut hasher).map_err(|e| ApiError::from(e).at(part_path))?;
+        progress.restart_at(existing);
+        part
+    } else {
+        let validator = response
+            .headers()
+            .get(ETAG)
+            .or(response.headers().get(LAST_MODIFIED))
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        write(validator_path, validator).map_err(|e| ApiError::from(e).at(validator_path))?;
+        progress.restart_at(0);
+        File::create(part_path).map_err(|e| ApiError::from(e).at(part_path))?
+    };
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let read = response
+            .read(&mut buf)
+            .map_err(|e| ApiError::from(e).at(&file.url))?;
+        if read == 0 {
+            break;
         }
-        Ok(())
+        hasher.update(&buf[..read]);
+        part.write_all(&buf[..read])
+            .map_err(|e| ApiError::from(e).at(part_path))?;
+        progress.advance(read as u64);
+    }
+    part.sync_all().map_err(|e| ApiError::from(e).at(part_path))?;
+    let size = metadata(part_path)
+        .map_err(|e| ApiError::from(e).at(part_path))?
+        .len();
+    Ok((base16ct::lower::encode_string(&hasher.finalize()), size))
+}
+
+/// true if the connection broke, reading the body wraps those errors in an io error
+fn is_interrupted(e: &ApiError) -> bool {
+    match e.kind() {
+        ApiErrorKind::Transport(_) => true,
+        ApiErrorKind::Io(e) => e.get_ref().is_some_and(|inner| inner.is::<reqwest::Error>()),
+        _ => false,
+    }
+}
+
+/// Move a bad download into the quarantine directory, returns where it ended up.
+///
+/// If it cannot be moved, it is deleted so it never ends up in the mods folder.
+fn quarantine_file(part_path: &str, path: &str, quarantine: &str) -> Option<String> {
+    let target = Path::new(quarantine).join(file_name(path));
+    let moved = create_dir_all(quarantine).is_ok()
+        && (rename(part_path, &target).is_ok() || copy(part_path, &target).is_ok());
+    let _ = remove_file(part_path);
+    match moved {
+        true => Some(target.to_string_lossy().to_string()),
+        false => None,
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::{serve, Response, TempDir};
+
+    const CONTENT: &[u8] = b"mod content";
+
+    fn sha512(content: &[u8]) -> String {
+        base16ct::lower::encode_string(&Sha512::digest(content))
+    }
+
+    /// a download of `CONTENT` into `dir`
+    fn download(dir: &TempDir, url: &str) -> FileDownload {
+        FileDownload {
+            path: dir.join("mod.jar").to_string_lossy().to_string(),
+            url: url.to_string() + "/mod.jar",
+            sha512: sha512(CONTENT),
+            size: CONTENT.len() as u64,
+        }
+    }
+
+    /// fetch the part of `file`, like `download_file` does
+    fn fetch(file: &FileDownload) -> Result<(String, u64), ApiError> {
+        let progress = Progress::hidden();
+        let file_progress = progress.start_file("mod.jar", file.size);
+        fetch_part(
+            &Client::new(),
+            file,
+            &(file.path.clone() + PART_SUFFIX),
+            &(file.path.clone() + VALIDATOR_SUFFIX),
+            &file_progress,
+        )
+    }
+
+    fn read(path: String) -> String {
+        read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn a_fresh_download_keeps_the_validator() {
+        let dir = TempDir::new("client-test-fresh");
+        let (url, requests) = serve(1, |_| Response::new(200, CONTENT).header("ETag", "\"v1\""));
+        let file = download(&dir, &url);
+
+        assert_eq!(fetch(&file).unwrap(), (sha512(CONTENT), 11));
+        assert_eq!(requests.recv().unwrap().header("range"), None);
+        assert_eq!(read(file.path.clone() + PART_SUFFIX), "mod content");
+        assert_eq!(read(file.path.clone() + VALIDATOR_SUFFIX), "\"v1\"");
+    }
+
+    #[test]
+    fn a_part_is_resumed_with_a_range_request() {
+        let dir = TempDir::new("client-test-resume");
+        let (url, requests) = serve(1, |_| Response::new(206, &CONTENT[4..]));
+        let file = download(&dir, &url);
+        write(file.path.clone() + PART_SUFFIX, &CONTENT[..4]).unwrap();
+        write(file.path.clone() + VALIDATOR_SUFFIX, "\"v1\"").unwrap();
+
+        // the hash covers the part that was already there
+        assert_eq!(fetch(&file).unwrap(), (sha512(CONTENT), 11));
+        let request = requests.recv().unwrap();
+        assert_eq!(request.header("range"), Some("bytes=4-"));
+        assert_eq!(request.header("if-range"), Some("\"v1\""));
+        assert_eq!(read(file.path.clone() + PART_SUFFIX), "mod content");
+    }
+
+    #[test]
+    fn a_part_without_validator_is_downloaded_again() {
+        let dir = TempDir::new("client-test-no-validator");
+        let (url, requests) = serve(1, |_| Response::new(200, CONTENT));
+        let file = download(&dir, &url);
+        write(file.path.clone() + PART_SUFFIX, b"old ").unwrap();
+
+        assert_eq!(fetch(&file).unwrap(), (sha512(CONTENT), 11));
+        assert_eq!(requests.recv().unwrap().header("range"), None);
+        assert_eq!(read(file.path.clone() + PART_SUFFIX), "mod content");
+    }
+
+    #[test]
+    fn a_changed_file_replaces_the_part() {
+        let dir = TempDir::new("client-test-changed");
+        // the server ignores the range, as If-Range asks it to for a changed file
+        let (url, requests) = serve(1, |_| Response::new(200, CONTENT).header("ETag", "\"v2\""));
+        let file = download(&dir, &url);
+        write(file.path.clone() + PART_SUFFIX, b"old ").unwrap();
+        write(file.path.clone() + VALIDATOR_SUFFIX, "\"v1\"").unwrap();
+
+        assert_eq!(fetch(&file).unwrap(), (sha512(CONTENT), 11));
+        assert_eq!(requests.recv().unwrap().header("range"), Some("bytes=4-"));
+        assert_eq!(read(file.path.clone() + PART_SUFFIX), "mod content");
+        assert_eq!(read(file.path.clone() + VALIDATOR_SUFFIX), "\"v2\"");
+    }
+
+    #[test]
+    fn an_unsatisfiable_range_restarts_the_download() {
+        let dir = TempDir::new("client-test-416");
+        let (url, requests) = serve(2, |request| match request.header("range") {
+            Some(_) => Response::new(416, ""),
+            None => Response::new(200, CONTENT).header("Last-Modified", "Sat, 01 Mar 2025 00:00:00 GMT"),
+        });
+        let file = download(&dir, &url);
+        write(file.path.clone() + PART_SUFFIX, b"mod content and more").unwrap();
+        write(file.path.clone() + VALIDATOR_SUFFIX, "\"v1\"").unwrap();
+
+        assert_eq!(fetch(&file).unwrap(), (sha512(CONTENT), 11));
+        assert_eq!(requests.recv().unwrap().header("range"), Some("bytes=20-"));
+        assert_eq!(requests.recv().unwrap().header("range"), None);
+        assert_eq!(read(file.path.clone() + PART_SUFFIX), "mod content");
+        assert_eq!(
+            read(file.path.clone() + VALIDATOR_SUFFIX),
+            "Sat, 01 Mar 2025 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn an_error_status_keeps_the_part() {
+        let dir = TempDir::new("client-test-error");
+        let (url, _requests) = serve(1, |_| Response::new(503, ""));
+        let file = download(&dir, &url);
+        write(file.path.clone() + PART_SUFFIX, b"mod ").unwrap();
+        write(file.path.clone() + VALIDATOR_SUFFIX, "\"v1\"").unwrap();
+
+        assert!(fetch(&file).is_err());
+        assert_eq!(read(file.path.clone() + PART_SUFFIX), "mod ");
+    }
+
+    #[test]
+    fn a_broken_connection_is_resumed() {
+        let dir = TempDir::new("client-test-broken");
+        let (url, requests) = serve(2, |request| match request.header("range") {
+            Some(_) => Response::new(206, &CONTENT[4..]),
+            None => Response::new(200, CONTENT).header("ETag", "\"v1\"").cut_off(4),
+        });
+        let file = download(&dir, &url);
+
+        Client::new()
+            .download_file(&file, &dir.join("quarantine").to_string_lossy(), &Progress::hidden())
+            .unwrap();
+
+        assert_eq!(requests.recv().unwrap().header("range"), None);
+        assert_eq!(requests.recv().unwrap().header("range"), Some("bytes=4-"));
+        assert_eq!(read(file.path.clone()), "mod content");
+        assert!(!Path::new(&(file.path.clone() + PART_SUFFIX)).exists());
+        assert!(!Path::new(&(file.path.clone() + VALIDATOR_SUFFIX)).exists());
+    }
+
+    /// download `body` for a file that should be `CONTENT`, returns the error
+    fn download_bad(dir: &TempDir, body: &'static [u8]) -> (FileDownload, ApiError) {
+        let (url, _requests) = serve(1, move |_| Response::new(200, body));
+        let file = download(dir, &url);
+        let quarantine = dir.join("quarantine").to_string_lossy().to_string();
+        let err = Client::new()
+            .download_file(&file, &quarantine, &Progress::hidden())
+            .unwrap_err();
+        (file, err)
+    }
+
+    #[test]
+    fn a_hash_mismatch_is_quarantined() {
+        let dir = TempDir::new("client-test-hash");
+        let (file, err) = download_bad(&dir, b"bad content");
+
+        let ApiErrorKind::HashMismatch { expected, actual, quarantined } = err.kind() else {
+            panic!("expected a hash mismatch, got {err:?}");
+        };
+        assert_eq!((expected, actual), (&sha512(CONTENT), &sha512(b"bad content")));
+        let quarantined = quarantined.clone().unwrap();
+        assert_eq!(Path::new(&quarantined), dir.join("quarantine/mod.jar"));
+        assert_eq!(read(quarantined), "bad content");
+        assert!(!Path::new(&file.path).exists());
+        assert!(!Path::new(&(file.path.clone() + PART_SUFFIX)).exists());
+        assert!(!Path::new(&(file.path.clone() + VALIDATOR_SUFFIX)).exists());
+    }
+
+    #[test]
+    fn a_size_mismatch_is_quarantined() {
+        let dir = TempDir::new("client-test-size");
+        let (file, err) = download_bad(&dir, b"mod content!");
+
+        let ApiErrorKind::SizeMismatch { expected, actual, quarantined } = err.kind() else {
+            panic!("expected a size mismatch, got {err:?}");
+        };
+        assert_eq!((expected, actual), (&11, &12));
+        assert_eq!(read(quarantined.clone().unwrap()), "mod content!");
+        assert!(!Path::new(&file.path).exists());
+        assert!(!Path::new(&(file.path.clone() + PART_SUFFIX)).exists());
+    }
+
+    #[test]
+    fn a_file_that_cannot_be_quarantined_is_removed() {
+        let dir = TempDir::new("client-test-no-quarantine");
+        let part = dir.join("mod.jar.part").to_string_lossy().to_string();
+        write(&part, b"bad content").unwrap();
+        // a file where the quarantine directory should be
+        let quarantine = dir.join("quarantine");
+        write(&quarantine, b"").unwrap();
+
+        assert_eq!(quarantine_file(&part, "mod.jar", &quarantine.to_string_lossy()), None);
+        assert!(!Path::new(&part).exists());
     }
 }
@@ -7,17 +7,49 @@ use std::{
 };
 use toml::{self, Table};
 
-use crate::mc_info::{MCVersion, LOADER, VT};
+use crate::{
+    mc_info::{MCVersion, LOADER, VT},
+    mrapi::defines::ApiEndpoint,
+    pack::{manifest::StaleAction, pack::InstallTarget, store::LinkMode},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Configuration {
     pub release_type: VT,
     pub loader: LOADER,
+    /// a loader from the config file that mapito does not know, `validate_config` reports it with a suggestion
+    #[serde(skip)]
+    pub unchecked_loader: Option<String>,
     pub download_path: String,
     pub pack_path: String,
     pub mc_ver: MCVersion,
-    pub staging: usize,
+    pub api: ApiEndpoint,
     pub install_path: Option<String>,
+    /// how often a request is retried after a 429 or 5xx response
+    pub max_retries: u32,
+    /// request timeout in seconds
+    pub timeout: u64,
+    /// personal access token for the modrinth api
+    pub token: Option<String>,
+    pub cache_path: String,
+    /// seconds a cached api response is used without asking the server again
+    pub cache_ttl: u64,
+    /// only use cached api responses
+    pub offline: bool,
+    /// downloads that fail their hash or size check are moved here
+    pub quarantine_path: String,
+    /// how many files are downloaded at the same time when installing a pack
+    pub workers: usize,
+    /// downloaded files are kept here, so every pack installs them from disk
+    pub store_path: String,
+    /// how files from the store are put into the install path
+    pub link_mode: LinkMode,
+    /// what happens to files a previous install put into the install path and the pack no longer has
+    pub stale_files: StaleAction,
+    /// also treat jars in the install path that mapito did not install as stale
+    pub remove_unknown: bool,
+    /// the side packs are installed for, mods that do not support it are skipped
+    pub install_target: InstallTarget,
 }
 
 pub fn configure() -> Result<Configuration, String> {
@@ -41,9 +73,12 @@ pub fn configure() -> Result<Configuration, String> {
 
     config = parse_config(body)?;
 
-    let mut config_fd = File::create(config_dir.as_path()).expect("open");
+    // a loader with a typo stays in the file until the user fixes it
+    if config.unchecked_loader.is_none() {
+        let mut config_fd = File::create(config_dir.as_path()).expect("open");
 
-    write!(&mut config_fd, "{}", toml::to_string(&config).unwrap()).expect("write config");
+        write!(&mut config_fd, "{}", toml::to_string(&config).unwrap()).expect("write config");
+    }
 
     Ok(config)
 }
@@ -73,12 +108,37 @@ fn parse_config(body: String) -> Result<Configuration, String> {
     for (key, value) in cfg_table {
         match key.as_str() {
             "release_type" => config.release_type = VT::from_str(value.as_str().unwrap()).unwrap(),
-            "loader" => config.loader = LOADER::from_str(value.as_str().unwrap()).unwrap(),
+            "loader" => {
+                let loader = value.as_str().unwrap_or_default();
+                match LOADER::from_str(loader) {
+                    Ok(v) => config.loader = v,
+                    Err(_) => config.unchecked_loader = Some(loader.to_string()),
+                }
+            }
             "download_path" => config.download_path = value.try_into().unwrap(),
             "pack_path" => config.pack_path = value.try_into().unwrap(),
             "mc_ver" => config.mc_ver = value.try_into().unwrap(),
-            "staging" => config.staging = value.try_into().unwrap(),
+            "api" => config.api = ApiEndpoint::from_str(value.as_str().unwrap())?,
+            // index into the old list of api urls, 1 was the staging server
+            "staging" => {
+                if value.as_integer() == Some(1) {
+                    config.api = ApiEndpoint::STAGING
+                }
+            }
             "install_path" => config.install_path = Some(value.try_into().unwrap()),
+            "max_retries" => config.max_retries = value.try_into().unwrap(),
+            "timeout" => config.timeout = value.try_into().unwrap(),
+            "token" => config.token = Some(value.try_into().unwrap()),
+            "cache_path" => config.cache_path = value.try_into().unwrap(),
+            "cache_ttl" => config.cache_ttl = value.try_into().unwrap(),
+            "offline" => config.offline = value.try_into().unwrap(),
+            "quarantine_path" => config.quarantine_path = value.try_into().unwrap(),
+            "workers" => config.workers = value.try_into().unwrap(),
+            "store_path" => config.store_path = value.try_into().unwrap(),
+            "link_mode" => config.link_mode = LinkMode::from_str(value.as_str().unwrap())?,
+            "stale_files" => config.stale_files = StaleAction::from_str(value.as_str().unwrap())?,
+            "remove_unknown" => config.remove_unknown = value.try_into().unwrap(),
+            "install_target" => config.install_target = InstallTarget::from_str(value.as_str().unwrap())?,
             &_ => println!("Warning: unused key '{key}' in config file."),
         }
     }
@@ -86,6 +146,12 @@ fn parse_config(body: String) -> Result<Configuration, String> {
     Ok(config)
 }
 
+impl Default for Configuration {
+    fn default() -> Self {
+        get_default_cfg()
+    }
+}
+
 fn get_default_cfg() -> Configuration {
     Configuration {
         release_type: VT::RELEASE,
@@ -102,8 +168,37 @@ fn get_default_cfg() -> Configuration {
             .unwrap()
             .to_owned(),
         loader: LOADER::FABRIC,
+        unchecked_loader: None,
         mc_ver: MCVersion::latest(),
-        staging: 0,
+        api: ApiEndpoint::PRODUCTION,
         install_path: None,
+        max_retries: 3,
+        timeout: 30,
+        token: None,
+        cache_path: env::home_dir()
+            .unwrap()
+            .join(".config/modrinth-apitool/cache")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        cache_ttl: 3600,
+        offline: false,
+        quarantine_path: env::home_dir()
+            .unwrap()
+            .join(".config/modrinth-apitool/quarantine")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        workers: 4,
+        store_path: env::home_dir()
+            .unwrap()
+            .join(".config/modrinth-apitool/store")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        link_mode: LinkMode::REFLINK,
+        stale_files: StaleAction::DISABLE,
+        remove_unknown: false,
+        install_target: InstallTarget::BOTH,
     }
 }
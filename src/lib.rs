@@ -0,0 +1,8 @@
+pub mod client;
+pub mod config;
+pub mod mc_info;
+pub mod mrapi;
+pub mod pack;
+pub mod util;
+
+pub use mrapi::client::ModrinthClient;
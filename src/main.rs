@@ -1,30 +1,37 @@
-mod cli;
-mod client;
-mod config;
-mod mc_info;
-mod mrapi;
-mod pack;
-mod util;
-
-use crate::client::Downloader;
-
-use argparse::{ArgumentParser, Store, StoreConst, StoreOption};
+use argparse::{ArgumentParser, Store, StoreConst, StoreOption, StoreTrue};
 use cli::{
-    input::{confirm_input, query_pack, read_line_to_string},
-    interactions::{prompt_for, prompt_multiple, search_mods},
-};
-use config::{configure, Configuration};
-use mc_info::{MCVersion, MVDescriptor, LOADER, VT};
-use mrapi::{
-    defines::Version,
-    interactions::{get_project_version, print_project_info, search_package},
+    download::download_mod,
+    input::{query_pack, read_line_to_string},
+    interactions::{print_project_info, search_package},
+    pack::{pack_creation_loop, pack_modification_loop, PackArgs},
+    publish::{publish, PublishArgs},
+    validate::{load_tags, validate_config},
 };
-use pack::{
-    create_pack,
-    pack::{Pack, PackAction},
-    update_pack,
+use mapito::{
+    config::{configure, Configuration},
+    mrapi::{defines::ApiEndpoint, tags::Tags},
+    pack::{
+        mrpack::{export_mrpack, import_mrpack},
+        pack::PackAction,
+        pack_from_folder,
+        packwiz::{export_packwiz, import_packwiz, PACK_FILE},
+        plan::Plan,
+        plan_update,
+        store::{collect_garbage, CacheAction},
+        update_pack,
+    },
+    say,
+    util::{error::ApiError, output::messages_to_stderr},
+    ModrinthClient,
 };
-use reqwest::blocking::Client;
+use std::process::exit;
+
+mod cli;
+// the test helpers of the library, they are not part of its api
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "util/testing.rs"]
+mod testing;
 
 fn main() {
     //variables set by arguments
@@ -32,7 +39,10 @@ fn main() {
     let mut search: String = String::new();
     let mut dl_id: String = String::new();
     let mut project_slug: String = String::new();
-    let mut pack_action: Option<PackAction> = None;
+    let mut pack_args = PackArgs::default();
+    let mut cache_action: Option<CacheAction> = None;
+    let mut publish_args = PublishArgs::default();
+    let mut loader_arg: String = String::new();
 
     //argument parser arg/opt setup
     {
@@ -45,13 +55,21 @@ fn main() {
             folder specified by you.",
         );
 
-        parser.refer(&mut config.staging).add_option(
-            &["-S", "--staging"],
-            StoreConst(0),
-            "If set, use the \
+        parser
+            .refer(&mut config.api)
+            .add_option(
+                &["-S", "--staging"],
+                StoreConst(ApiEndpoint::STAGING),
+                "If set, use the \
                 modrinth staging server rather than the normal api server. Used \
                 for testing",
-        );
+            )
+            .add_option(
+                &["--api"],
+                Store,
+                "The api server to use, one of: production, staging or the \
+                base url of any other server implementing the modrinth api",
+            );
 
         parser.refer(&mut search).add_option(
             &["-s", "--search"],
@@ -69,7 +87,7 @@ fn main() {
         parser.refer(&mut dl_id).add_option(
             &["-d", "--download"],
             Store,
-            "Download the mod given by it's ID/Slug.",
+            "Download the mod given by it's ID/Slug, use slug@version for a specific version.",
         );
 
         parser.refer(&mut config.mc_ver).add_option(
@@ -90,7 +108,7 @@ fn main() {
             "Path where pack files are stored",
         );
 
-        parser.refer(&mut config.loader).add_option(
+        parser.refer(&mut loader_arg).add_option(
             &["-l", "--loader"],
             Store,
             "The modloader to be used with the mod",
@@ -102,11 +120,76 @@ fn main() {
             "Get information about the specified project.",
         );
 
-        parser.refer(&mut pack_action).add_option(
+        parser.refer(&mut pack_args.action).add_option(
             &["-P", "--pack"],
             StoreOption,
             "Different interactions with Packs, options are\n
-            Create, Update, Modify, Install",
+            Create, Update, Modify, Install, Remove, Import, Export, Scan",
+        );
+
+        parser.refer(&mut cache_action).add_option(
+            &["--cache"],
+            StoreOption,
+            "Manage the store of downloaded files, options are\n
+            gc: remove files no pack uses anymore",
+        );
+
+        parser.refer(&mut config.link_mode).add_option(
+            &["--link"],
+            Store,
+            "How installed files are taken from the store, one of: hardlink, \
+            reflink, copy. Default: reflink, which copies where the file system \
+            cannot reflink",
+        );
+
+        parser.refer(&mut config.install_target).add_option(
+            &["--target"],
+            Store,
+            "The side '--pack install' installs the pack for, one of: client, \
+            server, both. Mods that do not support the side are skipped. \
+            Default: both",
+        );
+
+        parser.refer(&mut config.stale_files).add_option(
+            &["--stale"],
+            Store,
+            "What '--pack install' does with files an earlier install put into \
+            the install path that the pack no longer has, one of: remove, \
+            disable, keep. Default: disable, which renames them to .disabled",
+        );
+
+        parser.refer(&mut config.remove_unknown).add_option(
+            &["--remove-unknown"],
+            StoreTrue,
+            "Let '--pack install' also remove or disable jars in the install \
+            path that were not installed by mapito.",
+        );
+
+        parser.refer(&mut pack_args.dry_run).add_option(
+            &["--dry-run"],
+            StoreTrue,
+            "Print what '--pack install' or '--pack update' would download, \
+            replace and remove, without changing anything. Prompts and \
+            progress messages go to stderr, so stdout only has the plan.",
+        );
+
+        parser.refer(&mut pack_args.json).add_option(
+            &["--json"],
+            StoreTrue,
+            "Print the plan of '--dry-run' as JSON instead of text.",
+        );
+
+        parser.refer(&mut pack_args.changelog_path).add_option(
+            &["--export-changelog"],
+            StoreOption,
+            "Write the changelogs of all mods updated by '--pack update' to \
+            this file, as markdown.",
+        );
+
+        parser.refer(&mut config.workers).add_option(
+            &["--workers"],
+            Store,
+            "How many files are downloaded at the same time when installing a pack.",
         );
 
         parser.refer(&mut config.install_path).add_option(
@@ -115,277 +198,248 @@ fn main() {
             "The path of the modfolder the pack should be installed to.",
         );
 
+        parser.refer(&mut publish_args.file).add_option(
+            &["--publish"],
+            Store,
+            "Publish the given jar as a new version of the project given by \
+            --project. Game versions and loaders default to the configured \
+            ones.",
+        );
+
+        parser.refer(&mut publish_args.project).add_option(
+            &["--project"],
+            Store,
+            "ID/Slug of the project to publish to.",
+        );
+
+        parser.refer(&mut publish_args.version_number).add_option(
+            &["--version-number"],
+            Store,
+            "Version number of the published version.",
+        );
+
+        parser.refer(&mut publish_args.version_type).add_option(
+            &["--publish-type"],
+            Store,
+            "Type of the published version: release, beta or alpha. Default: release",
+        );
+
+        parser.refer(&mut publish_args.name).add_option(
+            &["--version-name"],
+            Store,
+            "Name of the published version. Default: the version number",
+        );
+
+        parser.refer(&mut publish_args.changelog).add_option(
+            &["--changelog"],
+            Store,
+            "Changelog of the published version, either text or a file to read it from.",
+        );
+
+        parser.refer(&mut publish_args.game_versions).add_option(
+            &["--game-versions"],
+            Store,
+            "Comma separated Minecraft versions the published version supports.",
+        );
+
+        parser.refer(&mut publish_args.loaders).add_option(
+            &["--loaders"],
+            Store,
+            "Comma separated loaders the published version supports.",
+        );
+
+        parser.refer(&mut publish_args.dependencies).add_option(
+            &["--dependencies"],
+            Store,
+            "Comma separated dependencies of the published version, as \
+            project[:type] where type is one of required, optional, \
+            incompatible, embedded. Default type: required",
+        );
+
+        parser.refer(&mut config.offline).add_option(
+            &["--offline"],
+            StoreTrue,
+            "Only use cached api responses, nothing is requested from the api.",
+        );
+
+        parser.refer(&mut config.cache_ttl).add_option(
+            &["--cache-ttl"],
+            Store,
+            "Seconds a cached api response is used before asking the api again.",
+        );
+
         parser.parse_args_or_exit();
     }
 
-    let client = Client::new();
+    // the plan of a dry run is the only thing on stdout
+    messages_to_stderr(pack_args.dry_run);
+
+    let result = match cache_action {
+        Some(action) => run_cache(action, &config),
+        None => run(
+            config,
+            search,
+            dl_id,
+            project_slug,
+            pack_args,
+            publish_args,
+            loader_arg,
+        ),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        exit(e.exit_code());
+    }
+}
+
+/// run a command on the store of downloaded files, it needs no api access
+fn run_cache(action: CacheAction, config: &Configuration) -> Result<(), ApiError> {
+    match action {
+        CacheAction::GC => {
+            let (removed, freed) = collect_garbage(config)?;
+            println!(
+                "Removed {removed} files no pack uses from the store, {:.2} MiB",
+                freed as f64 / 1048576_f64
+            );
+        }
+    }
+    Ok(())
+}
+
+/// print the plan of a dry run, as text or as JSON
+fn print_plan(plan: &Plan, json: bool) -> Result<(), ApiError> {
+    match json {
+        true => println!("{}", plan.to_json()?),
+        false => plan.print(),
+    }
+    Ok(())
+}
+
+/// run the command chosen by the arguments
+fn run(
+    mut config: Configuration,
+    search: String,
+    dl_id: String,
+    project_slug: String,
+    pack_args: PackArgs,
+    publish_args: PublishArgs,
+    loader_arg: String,
+) -> Result<(), ApiError> {
+    let client = ModrinthClient::from_config(&config)?;
 
     if !search.is_empty() {
-        search_package(&client, &search, config.staging, None, None);
-        return;
+        search_package(&client, &search, None, None)?;
+        return Ok(());
     }
 
-    if !dl_id.is_empty() {
-        let version_desc = MVDescriptor {
-            mc_ver: config.mc_ver,
-            version_types: vec![config.release_type.clone()],
-            loader: config.loader.clone(),
-        };
-        let dl_version: Version =
-            match get_project_version(&client, config.staging, dl_id, version_desc.clone()) {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("{}", e.to_string());
-                    return;
-                }
-            };
+    if !project_slug.is_empty() {
+        return print_project_info(&client, &project_slug);
+    }
 
-        let mut dependencies: Vec<Version> = Vec::new();
-        for dependency in dl_version.dependencies {
-            let dep_ver = match get_project_version(
-                &client,
-                config.staging,
-                dependency.project_id,
-                version_desc.clone(),
-            ) {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("get_project_version: {}", e.to_string());
-                    continue;
-                }
-            };
-            dependencies.push(dep_ver);
+    // only these commands use the Minecraft version and loader of the config or prompt for them
+    let uses_tags = !publish_args.file.is_empty()
+        || !dl_id.is_empty()
+        || matches!(pack_args.action, Some(PackAction::CREATE | PackAction::MODIFY));
+    let tags = match uses_tags {
+        true => {
+            let tags = load_tags(&client);
+            validate_config(&mut config, &tags, &loader_arg)?;
+            tags
         }
+        false => Tags::default(),
+    };
+    let config = &config;
 
-        let mut dl_size = (dl_version.files[0].size as f64 / 1048576 as f64).to_string();
-        dl_size.truncate(6);
+    if !publish_args.file.is_empty() {
+        let version = publish(&client, config, &publish_args, &tags)?;
         println!(
-            "Downloading: {}, {}\ntype: {}, downloads: {}, loader: {:?}\nsize: {} MiB",
-            dl_version.name,
-            dl_version.version_number,
-            dl_version.version_type.to_string(),
-            dl_version.downloads,
-            dl_version.loaders,
-            dl_size
-        );
-
-        if confirm_input() {
-            println!("Downloading to {}", &config.download_path);
-            let filename = dl_version.files[0].filename.as_str();
-            let path = &(config.download_path.clone() + "/" + filename);
-            let _ = client
-                .download_file(
-                    path,
-                    dl_version.files[0].url.as_str(),
-                    dl_version.files[0].hashes["sha512"]
-                        .to_string()
-                        .replace("\"", "")
-                        .as_str(),
-                )
-                .unwrap();
-        } else {
-            println!("Aborting");
-            return;
-        }
-
-        if !dependencies.is_empty() {
-            print!(
-                "Found the following dependencies:\n {}",
-                dependencies
-                    .iter()
-                    .map(|dep| dep.name.clone()
-                        + ", "
-                        + &(dep.files[0].size as f64 / 1048576 as f64).to_string()
-                        + "MB\n")
-                    .collect::<String>()
-            );
-            println!("Download these too?");
-            if confirm_input() {
-                for dep in dependencies {
-                    println!("Downloading {}", dep.name);
-                    let filename = dep.files[0].filename.as_str();
-                    let path = &(config.download_path.clone() + "/" + filename);
-                    let _ = client
-                        .download_file(
-                            path,
-                            dep.files[0].url.as_str(),
-                            dep.files[0].hashes["sha512"]
-                                .to_string()
-                                .replace("\"", "")
-                                .as_str(),
-                        )
-                        .unwrap();
-                }
-            }
-        }
-        return;
+            "Published {} {} ({})",
+            version.name, version.version_number, version.id
+        );
+        return Ok(());
     }
 
-    if !project_slug.is_empty() {
-        print_project_info(&client, config.staging, project_slug);
-        return;
+    if !dl_id.is_empty() {
+        return download_mod(&client, config, &dl_id);
     }
 
-    match pack_action {
-        Some(PackAction::CREATE) => pack_creation_loop(&client, &config),
+    match pack_args.action {
+        Some(PackAction::CREATE) => pack_creation_loop(&client, config, &tags),
         Some(PackAction::UPDATE) => {
-            println!("Please enter the name of the Pack you want to Update");
+            say!("Please enter the name of the Pack you want to Update");
             let name = read_line_to_string();
-            update_pack(&client, name, &config).expect("update_pack");
+            if pack_args.dry_run {
+                return print_plan(&plan_update(&client, name, config)?, pack_args.json);
+            }
+            let changelog = update_pack(&client, name, config)?;
+            match &pack_args.changelog_path {
+                Some(path) if !changelog.is_empty() => {
+                    changelog.write_markdown(path)?;
+                    println!("Wrote the changelog to {path}");
+                    Ok(())
+                }
+                Some(_) => {
+                    println!("No mods were updated, no changelog written");
+                    Ok(())
+                }
+                None => Ok(()),
+            }
         }
-        Some(PackAction::MODIFY) => pack_modification_loop(&client, &config),
+        Some(PackAction::MODIFY) => pack_modification_loop(&client, config, &tags),
         Some(PackAction::INSTALL) => {
-            if config.install_path.is_some() {
-                let pack = query_pack(PackAction::INSTALL, &config);
-                pack.install(&client, &config);
-            } else {
-                eprintln!("No install path given")
+            let pack = query_pack(PackAction::INSTALL, config)?;
+            match pack_args.dry_run {
+                true => print_plan(&Plan::install(&pack, config)?, pack_args.json),
+                false => pack.install(&client, config),
             }
         }
         Some(PackAction::REMOVE) => {
-            let pack = query_pack(PackAction::REMOVE, &config);
-            pack.remove(&config);
-        }
-        None => (),
-    }
-}
-
-fn pack_creation_loop(client: &Client, config: &Configuration) {
-    let mut version_desc = MVDescriptor {
-        mc_ver: MCVersion::new(),
-        version_types: vec![VT::RELEASE],
-        loader: LOADER::FABRIC,
-    };
-
-    let abort_msg = "Aborting pack creation.";
-
-    println!("Please enter the Name of the new Pack:");
-    let name = read_line_to_string();
-    version_desc.mc_ver = match prompt_for("Please enter the Minecraft version of this pack") {
-        Some(ver) => ver,
-        None => {
-            println!("{}", abort_msg);
-            return;
+            let pack = query_pack(PackAction::REMOVE, config)?;
+            pack.remove(config)
         }
-    };
-    version_desc.loader = match prompt_for("Please enter what loader you want to use") {
-        Some(loader) => loader,
-        None => {
-            println!("{}", abort_msg);
-            return;
+        Some(PackAction::IMPORT) => {
+            println!("Please enter the path of the .mrpack file or packwiz folder you want to import");
+            let path = read_line_to_string();
+            let pack = if path.ends_with(".mrpack") {
+                import_mrpack(&path, &client, config)?
+            } else {
+                import_packwiz(path.trim_end_matches(PACK_FILE), &client, config)?
+            };
+            pack.save(config)
         }
-    };
-    version_desc.version_types = prompt_multiple("Please enter one of 'release' 'beta' 'alpha'");
-    println!("Please confirm your input:\n Pack Name: {name}\n Minecraft version: {}\n Mod Loader: {}\n version types: {}",
-        version_desc.mc_ver,
-        version_desc.loader.to_string(),
-        version_desc.version_types.iter().map(|vt| vt.to_string() + " ").collect::<String>());
-    if !confirm_input() {
-        println!("Aborting pack Creation");
-        return;
-    }
-    println!(
-        "Now you can search for mods and add them to the pack, you can finish by entering 'q'"
-    );
-    let mut mods: Vec<String> = search_mods(client, config);
-
-    create_pack(&client, config.staging, name, version_desc, &mut mods, &config);
-    return;
-}
-
-fn pack_modification_loop(client: &Client, config: &Configuration) {
-    let mut pack = query_pack(PackAction::MODIFY, config);
-    loop {
-        println!("{}", pack.to_string(),);
-        match prompt_for::<char>("choose a category to modify:\n0 - Name\n1 - Version Info\n\tMinecraft Version\n\tVersion Types\n\tLoader\n2 - Mods\n") {
-            Some('0') => {
-                pack.remove(config);
-                match prompt_for::<String>("Enter a new name for the Pack.") {
-                    Some(name) => pack.name = name,
-                    None => println!("Name not changed."),
-                };
-                pack.save(config);
-                return;
+        Some(PackAction::EXPORT) => {
+            let mut pack = query_pack(PackAction::EXPORT, config)?;
+            match &pack.version {
+                Some(version) => println!("Please enter the version of the pack, leave empty to keep {version}"),
+                None => println!("Please enter the version of the pack"),
             }
-            Some('1') => {
-                let true_name = pack.name.clone();
-                pack.name = pack.name + "_tmp";
-                loop {
-                    println!("What do you want to change?");
-                    println!("  0 - Minecraft Version: {}", pack.version_info.mc_ver);
-                    println!(
-                        "  1 - Version Types: {}",
-                        pack.version_info
-                            .version_types
-                            .iter()
-                            .map(|vt| vt.to_string() + " ")
-                            .collect::<String>()
-                    );
-                    println!("  2 - Loader: {}", pack.version_info.loader.to_string());
-                    match prompt_for::<char>("") {
-                        Some('0') => {
-                            match prompt_for::<MCVersion>("enter a new Minecraft version for the Pack.") {
-                                Some(ver) => {pack.version_info.mc_ver = ver},
-                                None => {
-                                    println!("Version not changed.");
-                                },
-                            };
-                        }
-                        Some('1') => {
-                            println!("enter new version types for the Pack.");
-                            pack.version_info.version_types = prompt_multiple("Enter new version types for the Pack.");
-                        }
-                        Some('2') => {
-                            match prompt_for::<LOADER>("Please enter the loader you want to change to") {
-                                Some(loader) => pack.version_info.loader = loader,
-                                None => println!("Loader not changed."),
-                            };
-                        }
-                        None => break,
-                        _ => println!("unexpected input"),
-                    }
-                }
-                pack.save(config);
-                println!("updating mods.");
-                match update_pack(client, pack.name.clone(), config) {
-                    Ok(_) => {
-                        pack = Pack::open(&pack.name, config);
-                        pack.remove(config);
-                        pack.name = true_name;
-                        pack.save(config);
-                    }
-                    Err(_) => {
-                        pack.remove(config);
-                        pack.name = true_name;
-                    }
-                };
-                pack = Pack::open(&pack.name, config);
+            let version = read_line_to_string();
+            if !version.is_empty() {
+                pack.version = Some(version);
+                pack.save(config)?;
             }
-            Some('2') => loop {
-                pack.list_mods();
-                println!("Choose an Action:");
-                println!("  0 - add mods");
-                println!("  1 - remove a mod");
-                match prompt_for::<char>("") {
-                    Some('0') => {
-                        let mods = search_mods(client, config);
-                        for item in mods {
-                            pack.add_mod(&item, client, config.staging);
-                        }
-                        pack.save(config);
-                    }
-                    Some('1') => {
-                        println!("Enter which mod to remove:");
-                        pack.mods.remove(&read_line_to_string());
-                        pack.save(config);
-                        pack = Pack::open(&pack.name, config);
-                    }
-                    None => break,
-                    _ => println!("unexpected input"),
-                }
-            },
-            None => return,
-            _ => println!("unexpected input"),
+            println!("Please enter the path of the .mrpack file or packwiz folder to write, leave empty to use the download path");
+            let mut path = read_line_to_string();
+            if path.is_empty() {
+                path = config.download_path.clone()
+                    + "/"
+                    + &pack.name.to_lowercase().replace(" ", "-")
+                    + ".mrpack";
+            }
+            if path.ends_with(".mrpack") {
+                export_mrpack(&pack, &path, client.http())
+            } else {
+                export_packwiz(&pack, &path, client.http())
+            }
+        }
+        Some(PackAction::SCAN) => {
+            println!("Please enter the path of the mod folder you want to create a Pack from");
+            let folder = read_line_to_string();
+            println!("Please enter the Name of the new Pack:");
+            let name = read_line_to_string();
+            pack_from_folder(&client, name, &folder, config)?;
+            Ok(())
         }
+        None => Ok(()),
     }
 }
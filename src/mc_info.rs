@@ -112,9 +112,17 @@ impl PartialEq for MVDescriptor {
 impl MVDescriptor {
     pub fn check_version_compat(&self, version: &Version) -> bool {
         version.game_versions.contains(&self.mc_ver)
-            && version.loaders.contains(&self.loader)
+            && version.supports_loader(&self.loader)
             && self.version_types.contains(&version.version_type)
     }
+
+    /// like [`Self::check_version_compat`], but "latest" accepts every Minecraft version
+    pub fn accepts(&self, version: &Version) -> bool {
+        match self.mc_ver.is_latest() {
+            true => version.supports_loader(&self.loader),
+            false => self.check_version_compat(version),
+        }
+    }
 }
 
 /// This represents any Given minecraft version.
@@ -0,0 +1,16 @@
+use reqwest::header::HeaderValue;
+
+/// environment variable that takes precedence over the token in the config file
+pub const TOKEN_ENV: &str = "MODRINTH_TOKEN";
+
+/// Turn a personal access token into the value of the Authorization header.
+///
+/// The header is marked as sensitive, so it never shows up in debug output.
+pub fn token_header(token: &str) -> Result<HeaderValue, String> {
+    let mut value = match HeaderValue::from_str(token.trim()) {
+        Ok(v) => v,
+        Err(_) => return Err("The access token contains invalid characters".to_string()),
+    };
+    value.set_sensitive(true);
+    Ok(value)
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A cached api response, stored as one json file per url
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// unix time of the last time the server confirmed this response
+    pub fetched_at: u64,
+    pub body: String,
+}
+
+/// On disk cache for api responses.
+///
+/// Responses younger than `ttl` seconds are used without asking the server,
+/// in offline mode only cached responses are used, no matter their age.
+/// Responses fetched with an access token may contain private projects,
+/// they are kept apart, see [`ResponseCache::for_token`].
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    pub dir: PathBuf,
+    pub ttl: u64,
+    pub offline: bool,
+}
+
+impl ResponseCache {
+    pub fn new(dir: &str, ttl: u64, offline: bool) -> Self {
+        ResponseCache {
+            dir: PathBuf::from(dir),
+            ttl,
+            offline,
+        }
+    }
+
+    /// The cache for responses fetched with the given access token.
+    ///
+    /// They are stored in a subfolder named by a hash of the token, so
+    /// they are never served to runs with another token or none.
+    pub fn for_token(&self, token: &[u8]) -> Self {
+        let hash = base16ct::lower::encode_string(&Sha256::digest(token));
+        ResponseCache {
+            dir: self.dir.join("token-".to_string() + &hash[..16]),
+            ..self.clone()
+        }
+    }
+
+    /// the cached response for the url, if there is one
+    pub fn load(&self, url: &Url) -> Option<CacheEntry> {
+        let entry: CacheEntry = serde_json::from_str(&read_to_string(self.path(url)).ok()?).ok()?;
+        // guard against hash collisions
+        if entry.url == url.as_str() {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    /// true if the entry can be used without asking the server
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        now().saturating_sub(entry.fetched_at) < self.ttl
+    }
+
+    /// failing to write the cache is not fatal, the response is just not cached
+    pub fn store(&self, entry: &CacheEntry) {
+        let url = match Url::parse(&entry.url) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let _ = create_dir_all(&self.dir);
+        if let Ok(body) = serde_json::to_string(entry) {
+            let _ = write(self.path(&url), body);
+        }
+    }
+
+    fn path(&self, url: &Url) -> PathBuf {
+        self.dir.join(
+            base16ct::lower::encode_string(&Sha256::digest(url.as_str().as_bytes())) + ".json",
+        )
+    }
+}
+
+/// the current unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,273 @@
+use std::{sync::Mutex, thread::sleep, time::Duration};
+
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header::{HeaderValue, AUTHORIZATION, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode, Url,
+};
+
+use crate::{
+    config::Configuration,
+    util::error::{ApiError, ApiErrorKind},
+};
+
+use super::{
+    auth::{token_header, TOKEN_ENV},
+    cache::{now, CacheEntry, ResponseCache},
+    ratelimit::{retry_delay, RateLimit},
+};
+
+/// A client for the modrinth api.
+///
+/// It owns the http client, the base url of the api and the access token,
+/// keeps track of the rate limit and optionally caches responses on disk.
+/// None of its methods print anything.
+#[derive(Debug)]
+pub struct ModrinthClient {
+    http: Client,
+    base_url: String,
+    token: Option<HeaderValue>,
+    max_retries: u32,
+    rate_limit: Mutex<RateLimit>,
+    cache: Option<ResponseCache>,
+}
+
+impl ModrinthClient {
+    /// create a client for the api at `base_url`, e.g. `https://api.modrinth.com/v2`
+    pub fn new(base_url: &str) -> Self {
+        ModrinthClient {
+            http: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: None,
+            max_retries: 3,
+            rate_limit: Mutex::new(RateLimit::default()),
+            cache: None,
+        }
+    }
+
+    /// Create a client from the configuration.
+    ///
+    /// The access token is taken from the `MODRINTH_TOKEN` environment
+    /// variable, or the config if it is not set.
+    pub fn from_config(config: &Configuration) -> Result<Self, ApiError> {
+        let http = Client::builder()
+            .timeout(Duration::from_secs(config.timeout))
+            .build()?;
+        let mut client = ModrinthClient::new(config.api.url()).with_http(http);
+        client.set_max_retries(config.max_retries);
+        client.set_cache(Some(ResponseCache::new(
+            &config.cache_path,
+            config.cache_ttl,
+            config.offline,
+        )));
+        if let Some(token) = std::env::var(TOKEN_ENV).ok().or(config.token.clone()) {
+            client.set_token(&token)?;
+        }
+        Ok(client)
+    }
+
+    /// use the given http client, e.g. one with custom timeouts
+    pub fn with_http(mut self, http: Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Set the personal access token sent with every api request.
+    ///
+    /// The token is only sent to the api, never to the servers mod files are downloaded from.
+    pub fn set_token(&mut self, token: &str) -> Result<(), ApiError> {
+        self.token = Some(token_header(token).map_err(ApiError::invalid_data)?);
+        Ok(())
+    }
+
+    /// set how often a request is retried after a 429 or 5xx response
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// set the on disk cache for GET requests, None disables caching
+    pub fn set_cache(&mut self, cache: Option<ResponseCache>) {
+        self.cache = cache;
+    }
+
+    /// the http client, also used to download mod files
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// true if only cached responses may be used
+    pub fn is_offline(&self) -> bool {
+        self.cache.as_ref().is_some_and(|cache| cache.offline)
+    }
+
+    /// build the url of an api endpoint, with the given query parameters
+    pub fn url(&self, endpoint: &str, params: &[(&str, String)]) -> Result<Url, ApiError> {
+        let url = self.base_url.clone() + endpoint;
+        if params.is_empty() {
+            Url::parse(&url)
+        } else {
+            Url::parse_with_params(&url, params)
+        }
+        .map_err(|e| ApiError::invalid_data(format!("'{url}' is not a valid url: {e}")))
+    }
+
+    /// the cache for the responses of this client, it depends on the access token
+    fn cache(&self) -> Option<ResponseCache> {
+        let cache = self.cache.as_ref()?;
+        match &self.token {
+            Some(token) => Some(cache.for_token(token.as_bytes())),
+            None => Some(cache.clone()),
+        }
+    }
+
+    /// GET the given api url and return the response body.
+    ///
+    /// Stale cached responses are revalidated with their ETag and
+    /// Last-Modified date, only successful responses are cached. Responses
+    /// fetched with an access token are cached apart from the others.
+    pub fn get(&self, url: Url) -> Result<String, ApiError> {
+        let cache = self.cache();
+        let mut entry = cache.as_ref().and_then(|cache| cache.load(&url));
+
+        if let Some(cache) = &cache {
+            if cache.offline {
+                return match entry {
+                    Some(e) => Ok(e.body),
+                    None => Err(ApiError::from(ApiErrorKind::Offline).at(url)),
+                };
+            }
+            if let Some(e) = &entry {
+                if cache.is_fresh(e) {
+                    return Ok(e.body.clone());
+                }
+            }
+        }
+
+        let mut request = self.http.get(url.clone());
+        if let Some(e) = &entry {
+            if let Some(etag) = &e.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &e.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = self.send(request)?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(e) = entry.as_mut() {
+                e.fetched_at = now();
+            }
+        } else if status.is_success() {
+            let header = |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string())
+            };
+            let etag = header(ETAG);
+            let last_modified = header(LAST_MODIFIED);
+            entry = Some(CacheEntry {
+                url: url.to_string(),
+                etag,
+                last_modified,
+                fetched_at: now(),
+                body: response.text()?,
+            });
+        } else {
+            return Err(ApiError::http(status, response.text().unwrap_or_default()).at(url));
+        }
+
+        let entry = match entry {
+            Some(e) => e,
+            None => return Err(ApiError::http(status, String::new()).at(url)),
+        };
+        if let Some(cache) = &cache {
+            cache.store(&entry);
+        }
+        Ok(entry.body)
+    }
+
+    /// Send a request to the api, honoring the rate limit.
+    ///
+    /// If the last response said no requests are left, this waits for the
+    /// window to reset first. Responses with status 429 or 5xx are retried
+    /// with exponential backoff, until the retry limit is reached.
+    pub fn send(&self, request: RequestBuilder) -> Result<Response, ApiError> {
+        let request = match &self.token {
+            Some(token) => request.header(AUTHORIZATION, token.clone()),
+            None => request,
+        };
+        let mut attempt = 0;
+        loop {
+            let wait = self.rate_limit.lock().unwrap().wait_time();
+            if !wait.is_zero() {
+                sleep(wait);
+            }
+            let response = match request.try_clone() {
+                Some(req) => req.send()?,
+                None => return Ok(request.send()?),
+            };
+            self.rate_limit.lock().unwrap().update(response.headers());
+
+            if attempt >= self.max_retries {
+                return Ok(response);
+            }
+            match retry_delay(response.status(), response.headers(), attempt) {
+                Some(delay) => sleep(delay),
+                None => return Ok(response),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        mrapi::ratelimit::{REMAINING_HEADER, RESET_HEADER},
+        util::testing::{serve, Response},
+    };
+
+    #[test]
+    fn send_retries_until_the_limit() {
+        let (url, requests) = serve(2, |_| Response::new(503, ""));
+        let mut client = ModrinthClient::new(&url);
+        client.set_max_retries(1);
+
+        let response = client
+            .send(client.http().get(url + "/project/sodium"))
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn send_retries_a_rate_limited_request() {
+        let answered = AtomicUsize::new(0);
+        let (url, requests) = serve(2, move |_| match answered.fetch_add(1, Ordering::Relaxed) {
+            0 => Response::new(429, "")
+                .header(REMAINING_HEADER, "0")
+                .header(RESET_HEADER, "0"),
+            _ => Response::new(200, "{}"),
+        });
+        let client = ModrinthClient::new(&url);
+
+        let response = client
+            .send(client.http().get(url + "/project/sodium"))
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.try_iter().count(), 2);
+    }
+}
@@ -1,13 +1,20 @@
-pub const API_URL: [&str; 2] = [
-    "https://api.modrinth.com/v2",
-    "https://staging-api.modrinth.com/v2",
-];
+pub const PRODUCTION_URL: &str = "https://api.modrinth.com/v2";
+pub const STAGING_URL: &str = "https://staging-api.modrinth.com/v2";
 //API ENDPOINTS
 pub const SEARCH: &str = "/search";
 pub const PROJECT: &str = "/project";
 pub const VERSION: &str = "/version";
 pub const MEMBERS: &str = "/members";
+pub const VERSION_FILES: &str = "/version_files";
+pub const PROJECTS: &str = "/projects";
+pub const VERSIONS: &str = "/versions";
+pub const TAG_GAME_VERSION: &str = "/tag/game_version";
+pub const TAG_LOADER: &str = "/tag/loader";
+pub const TAG_CATEGORY: &str = "/tag/category";
 //API PARAMS
 pub const QUERY: &str = "query";
 pub const OFFSET: &str = "offset";
 pub const LIMIT: &str = "limit";
+pub const LOADERS: &str = "loaders";
+pub const GAME_VERSIONS: &str = "game_versions";
+pub const IDS: &str = "ids";
@@ -1,49 +1,217 @@
+use std::{fmt::Display, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 use crate::mc_info::MCVersion;
 use crate::mc_info::LOADER;
 use crate::mc_info::VT;
+use crate::util::error::ApiError;
+
+use super::constants::{PRODUCTION_URL, STAGING_URL};
+
+/// The api server mapito talks to, either one of the modrinth servers or any
+/// other server implementing the same api, like a self hosted labrinth or a proxy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiEndpoint {
+    PRODUCTION,
+    STAGING,
+    CUSTOM(String),
+}
+
+impl ApiEndpoint {
+    /// the base url all endpoints are appended to
+    pub fn url(&self) -> &str {
+        match self {
+            Self::PRODUCTION => PRODUCTION_URL,
+            Self::STAGING => STAGING_URL,
+            Self::CUSTOM(url) => url,
+        }
+    }
+}
+
+impl Display for ApiEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            Self::PRODUCTION => "production",
+            Self::STAGING => "staging",
+            Self::CUSTOM(url) => url,
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl FromStr for ApiEndpoint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "production" => Ok(Self::PRODUCTION),
+            "staging" => Ok(Self::STAGING),
+            url if url.starts_with("http://") || url.starts_with("https://") => {
+                Ok(Self::CUSTOM(s.trim_end_matches('/').to_string()))
+            }
+            _ => Err("api has to be 'production', 'staging' or an http(s) url".to_string()),
+        }
+    }
+}
+
+impl Serialize for ApiEndpoint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiEndpoint {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match Self::from_str(&String::deserialize(deserializer)?) {
+            Ok(api) => Ok(api),
+            Err(e) => Err(serde::de::Error::custom(e)),
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResp {
     pub hits: Vec<Value>,
-    offset: i32,
-    limit: i32,
-    total_hits: i32,
+    pub offset: i32,
+    pub limit: i32,
+    pub total_hits: i32,
 }
 
 //A specific version of a project
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Version {
+    pub id: String,
+    pub project_id: String,
     pub game_versions: Vec<MCVersion>,
-    pub loaders: Vec<LOADER>,
+    /// as modrinth names them, resource packs and shaders have loaders mapito does not know, like "minecraft" or "iris"
+    pub loaders: Vec<String>,
     pub name: String,
     pub version_number: String,
     pub downloads: u32,
     pub version_type: VT,
+    /// ISO 8601, so newer versions sort after older ones
+    #[serde(default)]
+    pub date_published: String,
+    #[serde(default)]
+    pub changelog: Option<String>,
     pub files: Vec<ApiFile>,
     pub dependencies: Vec<Dependency>,
 }
 
+impl Version {
+    pub fn supports_loader(&self, loader: &LOADER) -> bool {
+        self.loaders.iter().any(|name| *name == loader.to_string())
+    }
+
+    /// The file that is the mod itself.
+    ///
+    /// That is the file marked as primary, or if none is, the first file that
+    /// is neither an additional file like a resource pack nor a sources or dev jar.
+    pub fn primary_file(&self) -> Result<&ApiFile, ApiError> {
+        self.files
+            .iter()
+            .find(|file| file.primary)
+            .or_else(|| {
+                self.files
+                    .iter()
+                    .find(|file| file.file_type.is_none() && !file.is_auxiliary_jar())
+            })
+            .or(self.files.first())
+            .ok_or(ApiError::invalid_data(format!(
+                "'{} {}' has no files",
+                self.name, self.version_number
+            )))
+    }
+
+    /// every file except the primary one, e.g. sources jars or required resource packs
+    pub fn extra_files(&self) -> Vec<&ApiFile> {
+        let primary = self.primary_file().ok();
+        self.files
+            .iter()
+            .filter(|file| primary.is_none_or(|p| p.url != file.url))
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ApiFile {
     pub url: String,
     pub hashes: Map<String, Value>,
     pub filename: String,
     pub size: u64,
+    #[serde(default)]
+    pub primary: bool,
+    /// "required-resource-pack" or "optional-resource-pack", None for normal files
+    #[serde(default)]
+    pub file_type: Option<String>,
+}
+
+impl ApiFile {
+    /// jars that are published next to the mod but are not meant to be installed
+    fn is_auxiliary_jar(&self) -> bool {
+        let name = self.filename.trim_end_matches(".jar");
+        ["-sources", "-dev", "-javadoc"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Dependency {
-    pub project_id: String,
+    /// None for dependencies on an exact version of a project
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub version_id: Option<String>,
     pub dependency_type: String,
 }
 
+/// The metadata of a new version, sent as the `data` part when publishing.
+///
+/// Version types and loaders are written the way the api expects them ("release", "fabric").
+#[derive(Debug, Serialize, Clone)]
+pub struct NewVersion {
+    pub project_id: String,
+    pub name: String,
+    pub version_number: String,
+    pub changelog: Option<String>,
+    pub dependencies: Vec<Dependency>,
+    pub game_versions: Vec<MCVersion>,
+    #[serde(serialize_with = "serialize_display")]
+    pub version_type: VT,
+    #[serde(serialize_with = "serialize_display_vec")]
+    pub loaders: Vec<LOADER>,
+    pub featured: bool,
+    pub file_parts: Vec<String>,
+    pub primary_file: String,
+}
+
+fn serialize_display<S, T: Display>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&value.to_string())
+}
+
+fn serialize_display_vec<S, T: Display>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_seq(values.iter().map(|v| v.to_string()))
+}
+
 //A modrinth Project, this can be a mod, modpack, resourcepack or shader
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Project {
+    pub id: String,
     pub slug: String,
     pub project_type: String,
     pub team: String,
@@ -55,8 +223,68 @@ pub struct Project {
     pub downloads: u32,
     pub game_versions: Vec<MCVersion>,
     pub categories: Vec<String>,
-    pub loaders: Vec<LOADER>,
+    pub loaders: Vec<String>,
     pub source_url: Option<String>,
+    /// ids of every version of the project
+    #[serde(default)]
+    pub versions: Vec<String>,
+    #[serde(default)]
+    pub client_side: SideSupport,
+    #[serde(default)]
+    pub server_side: SideSupport,
+}
+
+/// Whether a project works on the client or on the server.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub enum SideSupport {
+    REQUIRED,
+    OPTIONAL,
+    UNSUPPORTED,
+    #[default]
+    UNKNOWN,
+}
+
+impl SideSupport {
+    pub fn is_unknown(&self) -> bool {
+        *self == Self::UNKNOWN
+    }
+}
+
+impl Display for SideSupport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            Self::REQUIRED => "required",
+            Self::OPTIONAL => "optional",
+            Self::UNSUPPORTED => "unsupported",
+            Self::UNKNOWN => "unknown",
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl FromStr for SideSupport {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "required" => Ok(Self::REQUIRED),
+            "optional" => Ok(Self::OPTIONAL),
+            "unsupported" => Ok(Self::UNSUPPORTED),
+            "unknown" => Ok(Self::UNKNOWN),
+            _ => Err("invalid side support".to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SideSupport {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match Self::from_str(&String::deserialize(deserializer)?) {
+            Ok(side) => Ok(side),
+            Err(e) => Err(serde::de::Error::custom(e)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -78,3 +306,107 @@ pub struct Member {
 pub struct User {
     pub username: String,
 }
+
+//Tags, the values modrinth knows for game versions, loaders and categories
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GameVersionTag {
+    pub version: String,
+    pub version_type: String,
+    pub date: String,
+    pub major: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoaderTag {
+    pub name: String,
+    pub supported_project_types: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryTag {
+    pub name: String,
+    pub project_type: String,
+    pub header: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(filename: &str, primary: bool, file_type: Option<&str>) -> ApiFile {
+        ApiFile {
+            url: format!("https://cdn.modrinth.com/{filename}"),
+            hashes: Map::new(),
+            filename: filename.to_string(),
+            size: 1,
+            primary,
+            file_type: file_type.map(str::to_string),
+        }
+    }
+
+    fn version(files: Vec<ApiFile>) -> Version {
+        Version {
+            id: "id".to_string(),
+            project_id: "project".to_string(),
+            game_versions: vec![],
+            loaders: vec![],
+            name: "Mod".to_string(),
+            version_number: "1.0.0".to_string(),
+            downloads: 0,
+            version_type: VT::RELEASE,
+            date_published: String::new(),
+            changelog: None,
+            files,
+            dependencies: vec![],
+        }
+    }
+
+    #[test]
+    fn primary_file_is_the_flagged_one() {
+        let version = version(vec![
+            file("mod-1.0.0.jar", false, None),
+            file("mod-1.0.0-fabric.jar", true, None),
+        ]);
+        assert_eq!(
+            version.primary_file().unwrap().filename,
+            "mod-1.0.0-fabric.jar"
+        );
+        let extra: Vec<&str> = version
+            .extra_files()
+            .iter()
+            .map(|f| f.filename.as_str())
+            .collect();
+        assert_eq!(extra, ["mod-1.0.0.jar"]);
+    }
+
+    #[test]
+    fn primary_file_skips_resource_packs_and_auxiliary_jars() {
+        let version = version(vec![
+            file("textures.zip", false, Some("required-resource-pack")),
+            file("mod-1.0.0-sources.jar", false, None),
+            file("mod-1.0.0-dev.jar", false, None),
+            file("mod-1.0.0.jar", false, None),
+        ]);
+        assert_eq!(version.primary_file().unwrap().filename, "mod-1.0.0.jar");
+        assert_eq!(version.extra_files().len(), 3);
+    }
+
+    #[test]
+    fn primary_file_falls_back_to_the_first_file() {
+        let version = version(vec![
+            file("mod-1.0.0-sources.jar", false, None),
+            file("mod-1.0.0-javadoc.jar", false, None),
+        ]);
+        assert_eq!(
+            version.primary_file().unwrap().filename,
+            "mod-1.0.0-sources.jar"
+        );
+    }
+
+    #[test]
+    fn primary_file_of_a_version_without_files() {
+        let version = version(vec![]);
+        assert!(version.primary_file().is_err());
+        assert!(version.extra_files().is_empty());
+    }
+}
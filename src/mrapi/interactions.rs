@@ -1,188 +1,210 @@
-use colored::Colorize;
-use reqwest::{blocking::Client, Url};
-use serde_json::{Value};
+use std::collections::HashMap;
 
-use crate::{util::error::ApiError, MVDescriptor};
+use reqwest::{
+    blocking::{multipart::Form, Response},
+    Url,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{
+    mc_info::MVDescriptor,
+    util::error::{ApiError, ApiErrorKind},
+};
 
 use super::{
-    constants::{API_URL, LIMIT, MEMBERS, OFFSET, PROJECT, QUERY, SEARCH, VERSION},
-    defines::{Member, Project, SearchResp, Version},
+    client::ModrinthClient,
+    constants::{
+        GAME_VERSIONS, IDS, LIMIT, LOADERS, MEMBERS, OFFSET, PROJECT, PROJECTS, QUERY, SEARCH,
+        TAG_CATEGORY, TAG_GAME_VERSION, TAG_LOADER, VERSION, VERSIONS, VERSION_FILES,
+    },
+    defines::{
+        CategoryTag, GameVersionTag, LoaderTag, Member, NewVersion, Project, SearchResp, Version,
+    },
 };
 
-pub fn search_package(client: &Client, query: &String, staging: usize, limit: Option<usize>, offset: Option<usize>) -> Option<Vec<String>> {
-    let par_limit = match limit {
-        Some(num) => {num.to_string()},
-        None => {"10".to_owned()},
-    };
-    
-    let par_offset = match offset {
-        Some(num) => {num.to_string()},
-        None => {"0".to_owned()},
-    };
-
-    let query = Url::parse_with_params(
-        (API_URL[staging].to_owned() + SEARCH).as_str(),
-        &[(QUERY, query), (LIMIT, &par_limit), (OFFSET, &par_offset)],
-    )
-    .unwrap();
-    let query_response = match client.get(query).send().unwrap().json::<SearchResp>() {
-        Ok(v) => v,
-        Err(_) => {
-            println!("Query failed.");
-            return None;
+impl ModrinthClient {
+    /// search for projects, returns one page of at most `limit` hits starting at `offset`
+    pub fn search(&self, query: &str, limit: usize, offset: usize) -> Result<SearchResp, ApiError> {
+        self.request(
+            SEARCH,
+            &[
+                (QUERY, query.to_string()),
+                (LIMIT, limit.to_string()),
+                (OFFSET, offset.to_string()),
+            ],
+        )
+    }
+
+    /// get a project by its id or slug
+    pub fn get_project(&self, project_slug: &str) -> Result<Project, ApiError> {
+        self.request(&(PROJECT.to_string() + "/" + project_slug), &[])
+    }
+
+    /// get the team members of a project
+    pub fn get_members(&self, project_slug: &str) -> Result<Vec<Member>, ApiError> {
+        self.request(&(PROJECT.to_string() + "/" + project_slug + MEMBERS), &[])
+    }
+
+    /// Get the newest version of a project that matches the given descriptor.
+    ///
+    /// Fails with a not found error if the project does not exist, and with
+    /// a no compatible version error if none of its versions match.
+    pub fn get_project_version(
+        &self,
+        project_slug: &str,
+        version_desc: &MVDescriptor,
+    ) -> Result<Version, ApiError> {
+        self.get_project_versions(project_slug, version_desc)?
+            .into_iter()
+            .next()
+            .ok_or(ApiError::no_compatible_version(project_slug))
+    }
+
+    /// get every version of a project that matches the given descriptor, newest first
+    pub fn get_project_versions(
+        &self,
+        project_slug: &str,
+        version_desc: &MVDescriptor,
+    ) -> Result<Vec<Version>, ApiError> {
+        // let the server filter by loader and game version, the result is still checked below
+        let mut params = vec![(LOADERS, format!("[\"{}\"]", version_desc.loader))];
+        if !version_desc.mc_ver.is_latest() {
+            params.push((GAME_VERSIONS, format!("[\"{}\"]", version_desc.mc_ver)));
         }
-    };
-
-    let mut slugs: Vec<String> = Vec::new();
-    let mut counter = 0;
-    for hit in query_response.hits {
-        let versions = hit["versions"].as_array().unwrap();
-        let latest = versions[versions.len() - 1].clone();
-        println!(
-            "{counter} {}|{},{}, MC-{}, by: {}, downloads: {}\n{}\n",
-            hit["slug"].to_string().replace("\"", "").green(),
-            hit["title"].to_string().replace("\"", ""),
-            hit["project_type"].to_string().replace("\"", ""),
-            latest.to_string().replace("\"", ""),
-            hit["author"].to_string().replace("\"", ""),
-            hit["downloads"].to_string().replace("\"", ""),
-            hit["description"]
-                .to_string()
-                .replace("\"", "")
-                .bright_black(),
-        );
-        counter += 1;
-        slugs.push(hit["slug"].to_string().replace("\"", ""));
-    }
-
-    Some(slugs)
-}
+        let versions: Vec<Version> = self.request(
+            &(PROJECT.to_owned() + "/" + project_slug + VERSION),
+            &params,
+        )?;
 
-fn request_api(
-    client: &Client,
-    staging: usize,
-    endpoint: &String,
-) -> Result<Value, serde_json::Error> {
-    let query = Url::parse(&(API_URL[staging].to_owned() + endpoint)).unwrap();
-
-    Ok(serde_json::from_str(
-        &client
-            .get(query)
-            .send()
-            .expect("send")
-            .text()
-            .expect("text"),
-    ))?
-}
+        if version_desc.mc_ver.is_latest() {
+            return Ok(versions);
+        }
+        Ok(versions
+            .into_iter()
+            .filter(|version| version_desc.check_version_compat(version))
+            .collect())
+    }
+
+    /// get a version by its id
+    pub fn get_version(&self, version_id: &str) -> Result<Version, ApiError> {
+        self.request(&(VERSION.to_string() + "/" + version_id), &[])
+    }
 
-pub fn print_project_info(client: &Client, staging: usize, project_slug: String) {
-    let project: Project =
-        get_project_info(client, staging, project_slug.clone()).expect("get_project_info");
-    let members: Vec<Member> = serde_json::from_value(
-        request_api(
-            client,
-            staging,
-            &(PROJECT.to_string() + "/" + &project_slug + MEMBERS),
+    /// get a version of a project by its id or version number, e.g. "mc1.21.5-0.6.13-fabric"
+    pub fn get_project_version_by_number(
+        &self,
+        project_slug: &str,
+        version: &str,
+    ) -> Result<Version, ApiError> {
+        self.request(
+            &(PROJECT.to_owned() + "/" + project_slug + VERSION + "/" + version),
+            &[],
         )
-        .expect("request_api"),
-    )
-    .expect("from_value");
-    println!(
-        "Project: {}, latest-{}, {}\n {}\n\n Released: {}\n Last Updated: {} \n \
-        loaders: {}\n supported versions: \n{} license: {}\n source: {}\n members:\n{}",
-        project.title,
-        project.game_versions.last().expect("last"),
-        project.project_type.green(),
-        project.description,
-        project.published.yellow(),
-        project.updated.yellow(),
-        project
-            .loaders
-            .iter()
-            .map(|e| e.to_string() + ",")
-            .collect::<String>(),
-        project
-            .game_versions
-            .iter()
-            .rev()
-            .take(10)
-            .map(|e| "  ".to_string() + &e.to_string() + "\n")
-            .collect::<String>(),
-        project.license.name,
-        match project.source_url {
-            Some(v) => {
-                v.bright_blue()
-            }
-            None => {
-                "none".to_string().red()
-            }
-        },
-        members
-            .iter()
-            .map(|mem| "  ".to_string() + &mem.user.username.clone() + ", " + &mem.role + "\n")
-            .collect::<String>(),
-    );
-}
+    }
+
+    /// Look up the versions belonging to the given sha512 file hashes in a single request.
+    ///
+    /// The returned map is keyed by hash, hashes modrinth does not know are missing from it.
+    pub fn get_versions_from_hashes(
+        &self,
+        hashes: &[String],
+    ) -> Result<HashMap<String, Version>, ApiError> {
+        let query = self.url(VERSION_FILES, &[])?;
+        if self.is_offline() {
+            return Err(ApiError::from(ApiErrorKind::Offline).at(query));
+        }
+        let response = self.send(
+            self.http()
+                .post(query.clone())
+                .json(&json!({ "hashes": hashes, "algorithm": "sha512" })),
+        )?;
+        parse_response(response, &query)
+    }
+
+    /// Get multiple projects by their ids or slugs in a single request
+    pub fn get_projects(&self, ids: &[String]) -> Result<Vec<Project>, ApiError> {
+        self.request_batch(PROJECTS, ids)
+    }
+
+    /// Get multiple versions by their ids in a single request
+    pub fn get_versions(&self, ids: &[String]) -> Result<Vec<Version>, ApiError> {
+        self.request_batch(VERSIONS, ids)
+    }
 
-pub fn get_project_version(
-    client: &Client,
-    staging: usize,
-    project_slug: String,
-    version_desc: MVDescriptor,
-) -> Result<Version, ApiError> {
-    let mut project_version: Option<Version> = None;
-    let versions: Vec<Version> = match serde_json::from_value(
-            match request_api(
-                            client,
-                            staging,
-                            &(PROJECT.to_owned() + "/" + &project_slug + VERSION),
-                        ) {
-                Ok(v) => {v},
-                Err(e) => {
-                    println!("{}", e.to_string());
-                    return Err(ApiError::not_found());
-                },
-            },
-        ) {
-        Ok(v) => {v},
-        Err(e) => {
-            println!("{}", e.to_string());
-            return Err(ApiError::invalid_data());
-        },
-    };
-    if version_desc.mc_ver.is_latest() {
-        project_version = Some(versions[0].clone());
-    } else {
-        for version in versions {
-            if version_desc.check_version_compat(&version)
-            {
-                project_version = Some(version.clone());
-                break;
-            }
+    /// Publish a new version of a project, uploading the given file as its primary file.
+    ///
+    /// This needs an access token with the permission to create versions.
+    pub fn create_version(
+        &self,
+        new_version: &NewVersion,
+        file_path: &str,
+    ) -> Result<Version, ApiError> {
+        let query = self.url(VERSION, &[])?;
+        if self.is_offline() {
+            return Err(ApiError::from(ApiErrorKind::Offline).at(query));
         }
+        let data = serde_json::to_string(new_version)?;
+        let form = Form::new()
+            .text("data", data)
+            .file(new_version.primary_file.clone(), file_path)
+            .map_err(|e| ApiError::from(e).at(file_path))?;
+        let response = self.send(self.http().post(query.clone()).multipart(form))?;
+        parse_response(response, &query)
+    }
+
+    /// every Minecraft version modrinth knows, newest first
+    pub fn get_game_versions(&self) -> Result<Vec<GameVersionTag>, ApiError> {
+        self.request(TAG_GAME_VERSION, &[])
     }
 
-    if project_version.is_none() {
-        return Err(ApiError::not_found());
+    /// every loader modrinth knows, together with the project types it supports
+    pub fn get_loaders(&self) -> Result<Vec<LoaderTag>, ApiError> {
+        self.request(TAG_LOADER, &[])
     }
 
-    Ok(project_version.expect("Unknown Error"))
+    /// every category modrinth knows
+    pub fn get_categories(&self) -> Result<Vec<CategoryTag>, ApiError> {
+        self.request(TAG_CATEGORY, &[])
+    }
+
+    /// GET an endpoint and deserialize the response
+    fn request<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, ApiError> {
+        let url = self.url(endpoint, params)?;
+        let body = self.get(url.clone())?;
+        parse_json(&body).map_err(|e| e.at(url))
+    }
+
+    fn request_batch<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        ids: &[String],
+    ) -> Result<Vec<T>, ApiError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids = serde_json::to_string(ids)?;
+        self.request(endpoint, &[(IDS, ids)])
+    }
 }
 
-pub fn get_project_info(
-    client: &Client,
-    staging: usize,
-    project_slug: String,
-) -> Result<Project, String> {
-    let project: Project = serde_json::from_value(
-        request_api(
-            client,
-            staging,
-            &(PROJECT.to_string() + "/" + &project_slug),
-        )
-        .expect("request_api"),
-    )
-    .expect("from_value");
+/// parse json, errors point to the field that could not be parsed
+pub fn parse_json<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    Ok(serde_path_to_error::deserialize(
+        &mut serde_json::Deserializer::from_str(body),
+    )?)
+}
 
-    Ok(project)
+/// parse the body of a successful response, unsuccessful ones become http errors
+fn parse_response<T: DeserializeOwned>(response: Response, url: &Url) -> Result<T, ApiError> {
+    let status = response.status();
+    let body = response.text().map_err(|e| ApiError::from(e).at(url))?;
+    if !status.is_success() {
+        return Err(ApiError::http(status, body).at(url));
+    }
+    parse_json(&body).map_err(|e| e.at(url))
 }
@@ -1,3 +1,8 @@
+pub mod auth;
+pub mod cache;
+pub mod client;
 pub mod constants;
 pub mod defines;
 pub mod interactions;
+pub mod ratelimit;
+pub mod tags;
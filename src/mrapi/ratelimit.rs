@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use reqwest::{header::HeaderMap, StatusCode};
+
+pub const LIMIT_HEADER: &str = "X-Ratelimit-Limit";
+pub const REMAINING_HEADER: &str = "X-Ratelimit-Remaining";
+pub const RESET_HEADER: &str = "X-Ratelimit-Reset";
+/// first delay between retries, doubled on every further attempt
+pub const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// What the last response told us about the rate limit.
+#[derive(Debug, Default)]
+pub struct RateLimit {
+    pub limit: Option<u32>,
+    pub remaining: Option<u32>,
+    pub reset_at: Option<Instant>,
+}
+
+impl RateLimit {
+    /// how long to wait before the next request, zero unless no requests are left
+    pub fn wait_time(&self) -> Duration {
+        match (self.remaining, self.reset_at) {
+            (Some(0), Some(reset_at)) => reset_at.saturating_duration_since(Instant::now()),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// remember the rate limit headers of a response
+    pub fn update(&mut self, headers: &HeaderMap) {
+        if let Some(limit) = header_value(headers, LIMIT_HEADER) {
+            self.limit = Some(limit);
+        }
+        if let Some(remaining) = header_value(headers, REMAINING_HEADER) {
+            self.remaining = Some(remaining);
+        }
+        if let Some(reset) = reset_in(headers) {
+            self.reset_at = Some(Instant::now() + reset);
+        }
+    }
+}
+
+/// Returns how long to wait before retrying a response, None if it should not be retried.
+///
+/// Responses with status 429 or 5xx are retried with exponential backoff,
+/// a 429 waits at least until the rate limit window resets.
+pub fn retry_delay(status: StatusCode, headers: &HeaderMap, attempt: u32) -> Option<Duration> {
+    if !(status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) {
+        return None;
+    }
+    let backoff = BASE_BACKOFF * 2u32.pow(attempt);
+    match (status, reset_in(headers)) {
+        (StatusCode::TOO_MANY_REQUESTS, Some(reset)) => Some(reset.max(backoff)),
+        _ => Some(backoff),
+    }
+}
+
+/// time until the rate limit window resets, the header holds seconds
+fn reset_in(headers: &HeaderMap) -> Option<Duration> {
+    header_value(headers, RESET_HEADER).map(|secs| Duration::from_secs(secs.into()))
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<u32> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn only_rate_limits_and_server_errors_are_retried() {
+        let none = HeaderMap::new();
+        assert_eq!(retry_delay(StatusCode::OK, &none, 0), None);
+        assert_eq!(retry_delay(StatusCode::NOT_FOUND, &none, 0), None);
+        assert_eq!(retry_delay(StatusCode::UNAUTHORIZED, &none, 0), None);
+        assert!(retry_delay(StatusCode::BAD_GATEWAY, &none, 0).is_some());
+        assert!(retry_delay(StatusCode::TOO_MANY_REQUESTS, &none, 0).is_some());
+    }
+
+    #[test]
+    fn backoff_doubles_with_every_attempt() {
+        let none = HeaderMap::new();
+        let delays: Vec<_> = (0..4)
+            .map(|attempt| retry_delay(StatusCode::SERVICE_UNAVAILABLE, &none, attempt).unwrap())
+            .collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn too_many_requests_waits_for_the_reset() {
+        let reset = headers(&[(RESET_HEADER, "7")]);
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, &reset, 0),
+            Some(Duration::from_secs(7))
+        );
+        // the backoff wins once it is longer than the window
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, &reset, 4),
+            Some(Duration::from_secs(8))
+        );
+        // only a 429 is about the rate limit window
+        assert_eq!(
+            retry_delay(StatusCode::INTERNAL_SERVER_ERROR, &reset, 0),
+            Some(BASE_BACKOFF)
+        );
+        let invalid = headers(&[(RESET_HEADER, "soon")]);
+        assert_eq!(
+            retry_delay(StatusCode::TOO_MANY_REQUESTS, &invalid, 1),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn wait_only_when_no_requests_are_left() {
+        let mut rate_limit = RateLimit::default();
+        assert_eq!(rate_limit.wait_time(), Duration::ZERO);
+
+        rate_limit.update(&headers(&[
+            (LIMIT_HEADER, "300"),
+            (REMAINING_HEADER, "12"),
+            (RESET_HEADER, "30"),
+        ]));
+        assert_eq!(
+            (rate_limit.limit, rate_limit.remaining),
+            (Some(300), Some(12))
+        );
+        assert_eq!(rate_limit.wait_time(), Duration::ZERO);
+
+        // headers missing from a response keep what was known
+        rate_limit.update(&headers(&[(REMAINING_HEADER, "0")]));
+        assert_eq!(
+            (rate_limit.limit, rate_limit.remaining),
+            (Some(300), Some(0))
+        );
+        let wait = rate_limit.wait_time();
+        assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+    }
+}
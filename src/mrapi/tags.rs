@@ -0,0 +1,232 @@
+use std::str::FromStr;
+
+use crate::{
+    mc_info::{MCVersion, LOADER},
+    util::error::ApiError,
+};
+
+use super::{
+    client::ModrinthClient,
+    defines::{GameVersionTag, LoaderTag},
+};
+
+/// the loaders mapito can install mods for
+pub const SUPPORTED_LOADERS: [&str; 4] = ["fabric", "quilt", "neoforge", "forge"];
+
+/// The game versions and loaders modrinth knows.
+///
+/// User input is checked against them, so that typos are caught before they
+/// end up in a pack. Empty lists accept everything, so a client that could
+/// not fetch the tags still works.
+#[derive(Debug, Default, Clone)]
+pub struct Tags {
+    pub game_versions: Vec<GameVersionTag>,
+    pub loaders: Vec<LoaderTag>,
+}
+
+impl Tags {
+    /// fetch all tags, the responses are cached like every other api response
+    pub fn fetch(client: &ModrinthClient) -> Result<Self, ApiError> {
+        Ok(Tags {
+            game_versions: client.get_game_versions()?,
+            loaders: client.get_loaders()?,
+        })
+    }
+
+    /// parse a Minecraft version and make sure it exists, 'latest' is always valid
+    pub fn parse_mc_version(&self, s: &str) -> Result<MCVersion, String> {
+        let version = MCVersion::from_str(s)?;
+        self.check_mc_version(&version)?;
+        Ok(version)
+    }
+
+    pub fn check_mc_version(&self, version: &MCVersion) -> Result<(), String> {
+        let name = version.to_string();
+        if version.is_latest()
+            || self.game_versions.is_empty()
+            || self.game_versions.iter().any(|tag| tag.version == name)
+        {
+            return Ok(());
+        }
+        Err(unknown(
+            "Minecraft version",
+            &name,
+            self.game_versions.iter().map(|tag| tag.version.as_str()),
+        ))
+    }
+
+    /// parse a loader, it has to be known to modrinth and supported by mapito
+    pub fn parse_loader(&self, s: &str) -> Result<LOADER, String> {
+        let name = s.trim().to_lowercase();
+        let known = self.loaders.is_empty()
+            || self.loaders.iter().any(|tag| {
+                tag.name == name && tag.supported_project_types.iter().any(|t| t == "mod")
+            });
+        match LOADER::from_str(&name) {
+            Ok(loader) if known => Ok(loader),
+            Ok(_) => Err(format!("The loader '{name}' is not known to modrinth")),
+            Err(_) if self.loaders.iter().any(|tag| tag.name == name) => Err(format!(
+                "The loader '{name}' is not supported, use one of: {}",
+                SUPPORTED_LOADERS.join(", ")
+            )),
+            Err(_) => Err(unknown("loader", &name, SUPPORTED_LOADERS.into_iter())),
+        }
+    }
+
+    pub fn check_loader(&self, loader: &LOADER) -> Result<(), String> {
+        self.parse_loader(&loader.to_string()).map(|_| ())
+    }
+}
+
+/// error message for an unknown value, suggesting the closest valid one
+fn unknown<'a>(what: &str, value: &str, candidates: impl Iterator<Item = &'a str>) -> String {
+    match closest(value, candidates) {
+        Some(suggestion) => format!("Unknown {what} '{value}', did you mean '{suggestion}'?"),
+        None => format!("Unknown {what} '{value}'"),
+    }
+}
+
+/// Find the candidate with the smallest edit distance to `input`.
+///
+/// On a tie the first candidate wins, tags are sorted newest first. Nothing
+/// is returned if even the closest candidate differs in most characters.
+pub fn closest<'a>(input: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates {
+        let distance = edit_distance(input, candidate);
+        if best.is_none_or(|(d, _)| distance < d) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.filter(|(distance, _)| *distance <= input.chars().count() / 2 + 1)
+        .map(|(_, candidate)| candidate)
+}
+
+/// levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags() -> Tags {
+        let game_version = |version: &str| GameVersionTag {
+            version: version.to_string(),
+            version_type: "release".to_string(),
+            date: String::new(),
+            major: false,
+        };
+        let loader = |name: &str, project_type: &str| LoaderTag {
+            name: name.to_string(),
+            supported_project_types: vec![project_type.to_string()],
+        };
+        Tags {
+            game_versions: vec![
+                game_version("1.21.5"),
+                game_version("1.21.4"),
+                game_version("1.20.1"),
+            ],
+            loaders: vec![
+                loader("fabric", "mod"),
+                loader("quilt", "mod"),
+                loader("liteloader", "mod"),
+                loader("iris", "shader"),
+            ],
+        }
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("fabric", "fabric"), 0);
+        assert_eq!(edit_distance("fabirc", "fabric"), 2);
+        assert_eq!(edit_distance("forge", "neoforge"), 3);
+        assert_eq!(edit_distance("", "quilt"), 5);
+    }
+
+    #[test]
+    fn closest_picks_the_smallest_distance() {
+        assert_eq!(
+            closest("fabirc", SUPPORTED_LOADERS.into_iter()),
+            Some("fabric")
+        );
+        assert_eq!(
+            closest("quillt", SUPPORTED_LOADERS.into_iter()),
+            Some("quilt")
+        );
+    }
+
+    #[test]
+    fn closest_prefers_the_first_candidate_on_a_tie() {
+        assert_eq!(
+            closest("1.21.9", ["1.21.5", "1.21.4"].into_iter()),
+            Some("1.21.5")
+        );
+    }
+
+    #[test]
+    fn closest_rejects_distant_candidates() {
+        assert_eq!(closest("xyz", SUPPORTED_LOADERS.into_iter()), None);
+        assert_eq!(closest("fabric", std::iter::empty()), None);
+    }
+
+    #[test]
+    fn unknown_mc_version_gets_a_suggestion() {
+        assert_eq!(
+            tags().parse_mc_version("1.21.9").unwrap_err(),
+            "Unknown Minecraft version '1.21.9', did you mean '1.21.5'?"
+        );
+        assert!(tags().parse_mc_version("1.20.1").is_ok());
+        assert!(tags().parse_mc_version("latest").is_ok());
+    }
+
+    #[test]
+    fn unknown_loader_gets_a_suggestion() {
+        assert_eq!(
+            tags().parse_loader("Fabirc").unwrap_err(),
+            "Unknown loader 'fabirc', did you mean 'fabric'?"
+        );
+        assert_eq!(tags().parse_loader(" Quilt ").unwrap(), LOADER::QUILT);
+    }
+
+    #[test]
+    fn loader_known_to_modrinth_but_not_mapito() {
+        assert!(tags()
+            .parse_loader("liteloader")
+            .unwrap_err()
+            .contains("not supported"));
+    }
+
+    #[test]
+    fn loader_not_known_to_modrinth() {
+        assert_eq!(
+            tags().parse_loader("forge").unwrap_err(),
+            "The loader 'forge' is not known to modrinth"
+        );
+    }
+
+    #[test]
+    fn empty_tags_accept_every_valid_input() {
+        assert!(Tags::default().parse_mc_version("1.99.1").is_ok());
+        assert_eq!(
+            Tags::default().parse_loader("forge").unwrap(),
+            LOADER::FORGE
+        );
+    }
+}
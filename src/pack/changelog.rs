@@ -0,0 +1,197 @@
+use std::fs::write;
+
+use crate::{mrapi::defines::Version, say, util::error::ApiError};
+
+/// The versions a mod went through in a pack update.
+///
+/// `versions` holds every version after `old_version` up to and including
+/// `new_version`, newest first.
+#[derive(Debug, Clone)]
+pub struct ModChangelog {
+    pub slug: String,
+    pub old_version: String,
+    pub new_version: String,
+    pub versions: Vec<Version>,
+}
+
+/// the changelogs of all mods updated in a pack
+#[derive(Debug, Clone, Default)]
+pub struct Changelog {
+    pub pack: String,
+    pub mods: Vec<ModChangelog>,
+}
+
+impl ModChangelog {
+    /// Collect the versions between the old one, given by id or number, and the newest.
+    ///
+    /// `versions` has to be sorted newest first. If the old version is not
+    /// among them, e.g. because the Minecraft version of the pack changed,
+    /// only the newest version is used.
+    pub fn between(
+        slug: &str,
+        old_id: &str,
+        old_number: &str,
+        versions: &[Version],
+    ) -> Option<Self> {
+        let newest = versions.first()?;
+        let mut between: Vec<Version> = versions
+            .iter()
+            .take_while(|version| version.id != old_id && version.version_number != old_number)
+            .cloned()
+            .collect();
+        if between.len() == versions.len() {
+            between.truncate(1);
+        }
+        Some(ModChangelog {
+            slug: slug.to_string(),
+            old_version: old_number.to_string(),
+            new_version: newest.version_number.clone(),
+            versions: between,
+        })
+    }
+
+    /// print the changelog of every version, indented below its version number
+    pub fn print(&self) {
+        for version in &self.versions {
+            say!("  {}:", version.version_number);
+            match changelog_text(version) {
+                Some(text) => {
+                    for line in text.lines() {
+                        say!("    {line}");
+                    }
+                }
+                None => say!("    no changelog"),
+            }
+        }
+    }
+}
+
+impl Changelog {
+    pub fn is_empty(&self) -> bool {
+        self.mods.is_empty()
+    }
+
+    /// the changelog as markdown, one section per mod and one subsection per version
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {} changelog\n", self.pack);
+        for mod_changelog in &self.mods {
+            markdown += &format!(
+                "\n## {} ({} -> {})\n",
+                mod_changelog.slug, mod_changelog.old_version, mod_changelog.new_version
+            );
+            for version in &mod_changelog.versions {
+                markdown += &format!("\n### {}\n\n", version.version_number);
+                markdown += changelog_text(version).unwrap_or("No changelog.");
+                markdown += "\n";
+            }
+        }
+        markdown
+    }
+
+    /// write the changelog as markdown to the given file
+    pub fn write_markdown(&self, path: &str) -> Result<(), ApiError> {
+        write(path, self.to_markdown()).map_err(|e| ApiError::from(e).at(path))
+    }
+}
+
+/// the trimmed changelog of a version, None if it has none
+fn changelog_text(version: &Version) -> Option<&str> {
+    version
+        .changelog
+        .as_deref()
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mc_info::VT;
+
+    fn version(number: &str, changelog: Option<&str>) -> Version {
+        Version {
+            id: format!("id-{number}"),
+            project_id: "sodium".to_string(),
+            game_versions: vec![],
+            loaders: vec![],
+            name: format!("Sodium {number}"),
+            version_number: number.to_string(),
+            downloads: 0,
+            version_type: VT::RELEASE,
+            date_published: String::new(),
+            changelog: changelog.map(str::to_string),
+            files: vec![],
+            dependencies: vec![],
+        }
+    }
+
+    fn numbers(changelog: &ModChangelog) -> Vec<&str> {
+        changelog
+            .versions
+            .iter()
+            .map(|version| version.version_number.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn between_stops_at_the_old_version() {
+        let versions = [
+            version("0.6.3", None),
+            version("0.6.2", None),
+            version("0.6.1", None),
+            version("0.6.0", None),
+        ];
+        let changelog = ModChangelog::between("sodium", "id-0.6.1", "0.6.1", &versions).unwrap();
+        assert_eq!(numbers(&changelog), ["0.6.3", "0.6.2"]);
+        assert_eq!(changelog.old_version, "0.6.1");
+        assert_eq!(changelog.new_version, "0.6.3");
+    }
+
+    #[test]
+    fn between_finds_the_old_version_by_id_or_number() {
+        let versions = [
+            version("0.6.3", None),
+            version("0.6.2", None),
+            version("0.6.1", None),
+        ];
+        let by_id = ModChangelog::between("sodium", "id-0.6.2", "", &versions).unwrap();
+        assert_eq!(numbers(&by_id), ["0.6.3"]);
+        let by_number = ModChangelog::between("sodium", "", "0.6.2", &versions).unwrap();
+        assert_eq!(numbers(&by_number), ["0.6.3"]);
+    }
+
+    #[test]
+    fn between_falls_back_to_the_newest_version() {
+        let versions = [version("0.6.3", None), version("0.6.2", None)];
+        let changelog = ModChangelog::between("sodium", "id-0.5.0", "0.5.0", &versions).unwrap();
+        assert_eq!(numbers(&changelog), ["0.6.3"]);
+    }
+
+    #[test]
+    fn between_is_empty_if_already_up_to_date() {
+        let versions = [version("0.6.3", None), version("0.6.2", None)];
+        let changelog = ModChangelog::between("sodium", "id-0.6.3", "0.6.3", &versions).unwrap();
+        assert!(changelog.versions.is_empty());
+        assert!(ModChangelog::between("sodium", "id-0.6.3", "0.6.3", &[]).is_none());
+    }
+
+    #[test]
+    fn markdown_has_a_section_per_mod_and_version() {
+        let changelog = Changelog {
+            pack: "survival".to_string(),
+            mods: vec![ModChangelog {
+                slug: "sodium".to_string(),
+                old_version: "0.6.1".to_string(),
+                new_version: "0.6.3".to_string(),
+                versions: vec![
+                    version("0.6.3", Some(" Fixed a crash\n")),
+                    version("0.6.2", Some("  ")),
+                ],
+            }],
+        };
+        assert_eq!(
+            changelog.to_markdown(),
+            "# survival changelog\n\n## sodium (0.6.1 -> 0.6.3)\n\n### 0.6.3\n\nFixed a crash\n\n### 0.6.2\n\nNo changelog.\n"
+        );
+    }
+}
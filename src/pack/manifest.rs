@@ -0,0 +1,265 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    fs::{read, read_dir, read_to_string, remove_file, rename, write},
+    io::ErrorKind,
+    path::Path,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::util::error::ApiError;
+
+/// name of the manifest in the install path
+pub const MANIFEST_FILE: &str = ".mapito-manifest.toml";
+/// suffix of disabled files, the loaders only load `.jar` files
+pub const DISABLED_SUFFIX: &str = ".disabled";
+
+/// What happens to files a previous install put into the install path, that the pack no longer has.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum StaleAction {
+    REMOVE,
+    /// rename them to `.disabled`, so they can be restored
+    DISABLE,
+    KEEP,
+}
+
+impl Display for StaleAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            Self::REMOVE => "remove",
+            Self::DISABLE => "disable",
+            Self::KEEP => "keep",
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl FromStr for StaleAction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "remove" => Ok(Self::REMOVE),
+            "disable" => Ok(Self::DISABLE),
+            "keep" => Ok(Self::KEEP),
+            _ => {
+                Err("invalid action for stale files, use one of: remove, disable, keep".to_string())
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StaleAction {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match Self::from_str(&String::deserialize(deserializer)?) {
+            Ok(action) => Ok(action),
+            Err(e) => Err(serde::de::Error::custom(e)),
+        }
+    }
+}
+
+/// The files mapito installed into a folder, so the next install knows which ones it may touch.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct InstallManifest {
+    pub pack: String,
+    /// [`manifest_name`] of a file to its sha512 hash
+    pub files: BTreeMap<String, String>,
+}
+
+impl InstallManifest {
+    /// read the manifest of an install path, an empty one if there is none yet
+    pub fn load(install_path: &str) -> Result<Self, ApiError> {
+        let path = Path::new(install_path).join(MANIFEST_FILE);
+        match read_to_string(&path) {
+            Ok(body) => toml::from_str(&body).map_err(|e| ApiError::from(e).at(path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(InstallManifest::default()),
+            Err(e) => Err(ApiError::from(e).at(path.display())),
+        }
+    }
+
+    pub fn save(&self, install_path: &str) -> Result<(), ApiError> {
+        let path = Path::new(install_path).join(MANIFEST_FILE);
+        write(&path, toml::to_string(self)?).map_err(|e| ApiError::from(e).at(path.display()))
+    }
+
+    /// Files this manifest lists, that are not in `current` and still exist.
+    ///
+    /// Names are the ones of [`manifest_name`].
+    ///
+    /// With `unknown`, jars that no manifest lists, i.e. added by the user,
+    /// count as stale as well.
+    pub fn stale_files(
+        &self,
+        install_path: &str,
+        current: &HashSet<String>,
+        unknown: bool,
+    ) -> Result<Vec<String>, ApiError> {
+        let mut stale: Vec<String> = self
+            .files
+            .keys()
+            .filter(|name| !current.contains(*name))
+            .filter(|name| Path::new(install_path).join(name).is_file())
+            .cloned()
+            .collect();
+        if unknown {
+            for entry in read_dir(install_path).map_err(|e| ApiError::from(e).at(install_path))? {
+                let name = entry
+                    .map_err(|e| ApiError::from(e).at(install_path))?
+                    .file_name()
+                    .to_string_lossy()
+                    .to_string();
+                if name.ends_with(".jar")
+                    && !current.contains(&name)
+                    && !self.files.contains_key(&name)
+                {
+                    stale.push(name);
+                }
+            }
+        }
+        Ok(stale)
+    }
+
+    /// true if the file is still the one mapito installed, user changes are never touched
+    pub fn is_unchanged(&self, install_path: &str, name: &str) -> bool {
+        let Some(sha512) = self.files.get(name) else {
+            return false;
+        };
+        read(Path::new(install_path).join(name))
+            .is_ok_and(|body| base16ct::lower::encode_string(&Sha512::digest(&body)) == *sha512)
+    }
+}
+
+/// The name a file is recorded under in the manifest of `install_path`.
+///
+/// Files in the install path are recorded by their name, files in other
+/// folders, like resource packs, by their full path.
+pub fn manifest_name(install_path: &str, path: &str) -> String {
+    match Path::new(path).strip_prefix(install_path) {
+        Ok(name) => name.to_string_lossy().to_string(),
+        Err(_) => path.to_string(),
+    }
+}
+
+/// remove or disable a stale file
+pub fn retire_file(install_path: &str, name: &str, action: StaleAction) -> Result<(), ApiError> {
+    let path = Path::new(install_path).join(name);
+    match action {
+        StaleAction::REMOVE => remove_file(&path),
+        StaleAction::DISABLE => {
+            let mut disabled = path.clone().into_os_string();
+            disabled.push(DISABLED_SUFFIX);
+            rename(&path, disabled)
+        }
+        StaleAction::KEEP => Ok(()),
+    }
+    .map_err(|e| ApiError::from(e).at(path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::TempDir;
+
+    /// an install path in the temp dir holding the given files, and the path as string
+    fn install_path(name: &str, files: &[&str]) -> (TempDir, String) {
+        let dir = TempDir::new(&format!("manifest-test-{name}"));
+        for file in files {
+            write(dir.join(file), file.as_bytes()).unwrap();
+        }
+        let path = dir.path_str();
+        (dir, path)
+    }
+
+    fn manifest(files: &[&str]) -> InstallManifest {
+        InstallManifest {
+            pack: "survival".to_string(),
+            files: files
+                .iter()
+                .map(|name| {
+                    (
+                        name.to_string(),
+                        base16ct::lower::encode_string(&Sha512::digest(name.as_bytes())),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn stale_files_are_listed_but_not_current() {
+        let (_dir, path) = install_path("listed", &["sodium.jar", "lithium.jar", "user.jar"]);
+        let manifest = manifest(&["sodium.jar", "lithium.jar", "removed.jar"]);
+        let stale = manifest
+            .stale_files(&path, &set(&["sodium.jar"]), false)
+            .unwrap();
+        // removed.jar is gone already, user.jar was never installed by mapito
+        assert_eq!(stale, ["lithium.jar"]);
+    }
+
+    #[test]
+    fn unknown_jars_are_only_stale_if_asked_for() {
+        let (_dir, path) = install_path("unknown", &["sodium.jar", "user.jar", "notes.txt"]);
+        let manifest = manifest(&["sodium.jar"]);
+        let mut stale = manifest.stale_files(&path, &set(&[]), true).unwrap();
+        stale.sort();
+        assert_eq!(stale, ["sodium.jar", "user.jar"]);
+        assert!(manifest
+            .stale_files(&path, &set(&["sodium.jar"]), false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn changed_files_are_not_unchanged() {
+        let (_dir, path) = install_path("changed", &["sodium.jar", "lithium.jar"]);
+        let manifest = manifest(&["sodium.jar", "lithium.jar"]);
+        write(Path::new(&path).join("lithium.jar"), b"edited").unwrap();
+        assert!(manifest.is_unchanged(&path, "sodium.jar"));
+        assert!(!manifest.is_unchanged(&path, "lithium.jar"));
+        assert!(!manifest.is_unchanged(&path, "user.jar"));
+    }
+
+    #[test]
+    fn manifest_survives_a_save_and_load() {
+        let (_dir, path) = install_path("save", &[]);
+        assert!(InstallManifest::load(&path).unwrap().files.is_empty());
+        manifest(&["sodium.jar"]).save(&path).unwrap();
+        let loaded = InstallManifest::load(&path).unwrap();
+        assert_eq!(loaded.pack, "survival");
+        assert_eq!(loaded.files, manifest(&["sodium.jar"]).files);
+    }
+
+    #[test]
+    fn retired_files_are_removed_or_disabled() {
+        let (_dir, path) = install_path("retire", &["sodium.jar", "lithium.jar", "iris.jar"]);
+        retire_file(&path, "sodium.jar", StaleAction::REMOVE).unwrap();
+        retire_file(&path, "lithium.jar", StaleAction::DISABLE).unwrap();
+        retire_file(&path, "iris.jar", StaleAction::KEEP).unwrap();
+        let dir = Path::new(&path);
+        assert!(!dir.join("sodium.jar").exists());
+        assert!(!dir.join("lithium.jar").exists());
+        assert!(dir.join("lithium.jar.disabled").is_file());
+        assert!(dir.join("iris.jar").is_file());
+    }
+
+    #[test]
+    fn manifest_names_are_relative_to_the_install_path() {
+        assert_eq!(
+            manifest_name("/mc/mods", "/mc/mods/sodium.jar"),
+            "sodium.jar"
+        );
+        assert_eq!(
+            manifest_name("/mc/mods", "/mc/resourcepacks/faithful.zip"),
+            "/mc/resourcepacks/faithful.zip"
+        );
+    }
+}
@@ -1,22 +1,42 @@
-use std::fs::{create_dir_all, File};
-use std::io::{Write};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{read_dir, File},
+    io::copy,
+    path::Path,
+    str::FromStr,
+};
 
-use pack::Pack;
+use changelog::{Changelog, ModChangelog};
+use pack::{InstallTarget, Pack};
+use plan::Plan;
 use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha512};
 use toml::{self};
 
-use crate::mc_info::VT;
+use crate::client::FileDownload;
+use crate::mc_info::{MCVersion, LOADER, VT};
+use crate::mrapi::defines::{ApiFile, Project, SideSupport, Version};
+use crate::say;
 use crate::util::error::ApiError;
 use crate::{
     config::Configuration,
-    mrapi::
-        interactions::get_project_version
-    ,
-    MVDescriptor,
+    mc_info::MVDescriptor,
+    mrapi::client::ModrinthClient,
 };
 
+pub mod changelog;
+pub mod manifest;
+pub mod mrpack;
 pub mod pack;
+pub mod packwiz;
+pub mod plan;
+pub mod store;
+
+/// the folder mods are installed to, relative to the instance
+pub const MODS_FOLDER: &str = "mods";
+pub const RESOURCEPACKS_FOLDER: &str = "resourcepacks";
 
 #[derive(Deserialize, Serialize, Debug)]
 struct PackMod {
@@ -26,6 +46,72 @@ struct PackMod {
     file_url: String,
     file_name: String,
     sha512: String,
+    #[serde(default)]
+    sha1: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    project_id: String,
+    #[serde(default)]
+    version_id: String,
+    /// pinned mods stay on `version_id` when the pack is updated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+    /// files of the version besides the mod itself that are installed as well
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    extra_files: Vec<ExtraFile>,
+    #[serde(default, skip_serializing_if = "SideSupport::is_unknown")]
+    client_side: SideSupport,
+    #[serde(default, skip_serializing_if = "SideSupport::is_unknown")]
+    server_side: SideSupport,
+    /// folder of the file relative to the instance, None for the mods folder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    folder: Option<String>,
+    /// files not hosted on modrinth, they are installed but never updated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    external: bool,
+}
+
+/// A file of a mod version that is not the mod jar, the user has to opt in to these.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct ExtraFile {
+    file_name: String,
+    file_url: String,
+    sha512: String,
+    #[serde(default)]
+    sha1: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    file_type: Option<String>,
+}
+
+impl From<&ApiFile> for ExtraFile {
+    fn from(file: &ApiFile) -> Self {
+        ExtraFile {
+            file_name: file.filename.clone(),
+            file_url: file.url.clone(),
+            sha512: file_hash(file, "sha512"),
+            sha1: file_hash(file, "sha1"),
+            size: file.size,
+            file_type: file.file_type.clone(),
+        }
+    }
+}
+
+impl ExtraFile {
+    /// the folder the file belongs in, if it is not the one of its mod
+    fn folder(&self) -> Option<&str> {
+        match self.file_type.as_deref() {
+            Some("required-resource-pack" | "optional-resource-pack") => Some(RESOURCEPACKS_FOLDER),
+            _ => None,
+        }
+    }
+
+    /// path of the file relative to the instance, `mod_folder` is the folder of its mod
+    fn instance_path(&self, mod_folder: Option<&str>) -> String {
+        self.folder().or(mod_folder).unwrap_or(MODS_FOLDER).to_string() + "/" + &self.file_name
+    }
 }
 
 impl PartialEq for PackMod {
@@ -34,80 +120,699 @@ impl PartialEq for PackMod {
     }
 }
 
+impl PackMod {
+    /// create the entry for the given file of a project version
+    fn from_file(version: &Version, file: &ApiFile) -> Self {
+        PackMod {
+            name: version.name.clone(),
+            verstion_type: version.version_type.clone(),
+            version_number: version.version_number.clone(),
+            file_url: file.url.clone(),
+            file_name: file.filename.clone(),
+            sha512: file_hash(file, "sha512"),
+            sha1: file_hash(file, "sha1"),
+            size: file.size,
+            project_id: version.project_id.clone(),
+            version_id: version.id.clone(),
+            pinned: false,
+            extra_files: Vec::new(),
+            client_side: SideSupport::UNKNOWN,
+            server_side: SideSupport::UNKNOWN,
+            folder: None,
+            external: false,
+        }
+    }
+
+    /// record the sides the project of this mod supports
+    fn set_sides(&mut self, project: &Project) {
+        self.client_side = project.client_side;
+        self.server_side = project.server_side;
+    }
+
+    /// path of the mod file relative to the instance
+    fn instance_path(&self) -> String {
+        self.folder.as_deref().unwrap_or(MODS_FOLDER).to_string() + "/" + &self.file_name
+    }
+
+    /// false for files in other folders, like resource packs or shaders
+    fn in_mods_folder(&self) -> bool {
+        self.folder.as_deref().is_none_or(|folder| folder == MODS_FOLDER)
+    }
+
+    /// keep where and for which sides the old entry of the mod was installed
+    fn keep_install_info(&mut self, other: &PackMod) {
+        self.client_side = other.client_side;
+        self.server_side = other.server_side;
+        self.folder = other.folder.clone();
+    }
+
+    /// false if the mod is marked unsupported on the target side, mods with unknown sides are always installed
+    fn supports(&self, target: InstallTarget) -> bool {
+        match target {
+            InstallTarget::CLIENT => self.client_side != SideSupport::UNSUPPORTED,
+            InstallTarget::SERVER => self.server_side != SideSupport::UNSUPPORTED,
+            InstallTarget::BOTH => true,
+        }
+    }
+
+    /// The mod file and the chosen extra files, `dir` is the mods folder.
+    ///
+    /// Files in other folders, like resource packs, go next to the mods folder.
+    fn downloads(&self, dir: &str) -> Vec<FileDownload> {
+        let mut downloads = vec![FileDownload {
+            path: folder_path(dir, self.folder.as_deref()) + "/" + &self.file_name,
+            url: self.file_url.clone(),
+            sha512: self.sha512.clone(),
+            size: self.size,
+        }];
+        downloads.extend(self.extra_files.iter().map(|extra| FileDownload {
+            path: folder_path(dir, extra.folder().or(self.folder.as_deref())) + "/" + &extra.file_name,
+            url: extra.file_url.clone(),
+            sha512: extra.sha512.clone(),
+            size: extra.size,
+        }));
+        downloads
+    }
+
+    /// create the entry for the primary file of a project version
+    fn from_version(version: &Version) -> Result<Self, ApiError> {
+        Ok(PackMod::from_file(version, version.primary_file()?))
+    }
+
+    /// Choose the same extra files from a new version of the mod.
+    ///
+    /// Files are matched by their file type, the ones without a counterpart
+    /// in the new version are dropped and returned.
+    fn keep_extra_files(&mut self, old: &PackMod, version: &Version) -> Vec<String> {
+        let mut candidates = version.extra_files();
+        let mut dropped = Vec::new();
+        for extra in &old.extra_files {
+            match candidates.iter().position(|f| f.file_type == extra.file_type) {
+                Some(i) => self.extra_files.push(candidates.remove(i).into()),
+                None => dropped.push(extra.file_name.clone()),
+            }
+        }
+        dropped
+    }
+
+    /// download the mod file to fill in whichever of sha1, sha512 and size is missing,
+    /// the hashes that are already known have to match the download
+    fn fetch_file_info(&mut self, client: &Client) -> Result<(), ApiError> {
+        let body = client.get(&self.file_url).send()?.error_for_status()?.bytes()?;
+        let sha512 = base16ct::lower::encode_string(&Sha512::digest(&body));
+        let sha1 = base16ct::lower::encode_string(&Sha1::digest(&body));
+        if (!self.sha512.is_empty() && self.sha512 != sha512)
+            || (!self.sha1.is_empty() && self.sha1 != sha1)
+        {
+            return Err(ApiError::invalid_data(format!(
+                "'{}' does not match its hash",
+                self.file_name
+            )));
+        }
+        self.sha512 = sha512;
+        self.sha1 = sha1;
+        self.size = body.len() as u64;
+        Ok(())
+    }
+}
+
+/// split `slug@version` into the slug and the version id or number
+pub fn split_version(spec: &str) -> (&str, Option<&str>) {
+    match spec.split_once('@') {
+        Some((slug, version)) if !version.is_empty() => (slug, Some(version)),
+        Some((slug, _)) => (slug, None),
+        None => (spec, None),
+    }
+}
+
+/// tell the user which extra files of a mod are gone after an update
+fn report_dropped(key: &str, dropped: Vec<String>) {
+    if !dropped.is_empty() {
+        say!(
+            "The new version of {key} has no counterpart for: {}",
+            dropped.join(", ")
+        );
+    }
+}
+
+/// The folder to put a file of the given instance folder in, `mods_dir` is the mods folder.
+///
+/// Other folders are next to the mods folder, in the instance root.
+fn folder_path(mods_dir: &str, folder: Option<&str>) -> String {
+    match folder {
+        None | Some(MODS_FOLDER) => mods_dir.to_string(),
+        Some(folder) => match Path::new(mods_dir).parent() {
+            Some(instance) => instance.join(folder).to_string_lossy().to_string(),
+            None => folder.to_string(),
+        },
+    }
+}
+
+/// Split a path relative to the instance into its folder and file name.
+///
+/// The folder is None for the mods folder. Absolute paths and paths leaving
+/// the instance are rejected.
+fn split_instance_path(path: &str) -> Result<(Option<String>, String), ApiError> {
+    let invalid = || ApiError::invalid_data(format!("Invalid path '{path}'"));
+    if path.starts_with('/') || path.contains('\\') || path.split('/').any(|part| part == "..") {
+        return Err(invalid());
+    }
+    match path.rsplit_once('/') {
+        Some((_, "")) => Err(invalid()),
+        Some((MODS_FOLDER, name)) => Ok((None, name.to_string())),
+        Some((folder, name)) => Ok((Some(folder.to_string()), name.to_string())),
+        None if path.is_empty() => Err(invalid()),
+        None => Ok((None, path.to_string())),
+    }
+}
+
+/// The versions of files modrinth knows by their hash, and the projects of those versions.
+#[derive(Debug, Default)]
+struct IdentifiedFiles {
+    /// by sha512 hash, hashes modrinth does not know are missing
+    versions: HashMap<String, Version>,
+    /// by project id
+    projects: HashMap<String, Project>,
+}
+
+impl IdentifiedFiles {
+    /// the version and the project of the file with the given sha512 hash
+    fn get(&self, sha512: &str) -> Option<(&Version, &ApiFile, &Project)> {
+        let version = self.versions.get(sha512)?;
+        let file = version.files.iter().find(|f| file_hash(f, "sha512") == sha512)?;
+        Some((version, file, self.projects.get(&version.project_id)?))
+    }
+}
+
+/// Look up the versions of the given sha512 hashes and their projects, with one request each.
+fn identify_files(client: &ModrinthClient, hashes: &[String]) -> Result<IdentifiedFiles, ApiError> {
+    if hashes.is_empty() {
+        return Ok(IdentifiedFiles::default());
+    }
+    let versions = client.get_versions_from_hashes(hashes)?;
+    let mut project_ids: Vec<String> = versions.values().map(|v| v.project_id.clone()).collect();
+    project_ids.sort();
+    project_ids.dedup();
+    let projects = match project_ids.is_empty() {
+        true => Vec::new(),
+        false => client.get_projects(&project_ids)?,
+    };
+    Ok(IdentifiedFiles {
+        versions,
+        projects: projects.into_iter().map(|p| (p.id.clone(), p)).collect(),
+    })
+}
+
+/// the last component of a download path
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// the hash of a file with the given algorithm, empty if the api did not provide it
+pub fn file_hash(file: &ApiFile, algorithm: &str) -> String {
+    file.hashes
+        .get(algorithm)
+        .and_then(|hash| hash.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Create a pack with the given mods and their dependencies.
+///
+/// Mods given as `slug@version` are pinned to that version, even if it is
+/// not made for the Minecraft version of the pack.
 pub fn create_pack(
-    client: &Client,
-    staging: usize,
+    client: &ModrinthClient,
     name: String,
     version_desc: MVDescriptor,
     mods: &Vec<String>,
     config: &Configuration,
-) {
+) -> Result<(), ApiError> {
     let mut pack = Pack::new();
     pack.name = name;
     pack.version_info = version_desc.clone();
-    
 
     for mc_mod in mods {
-        if !pack.add_mod(mc_mod, client, staging).contains(&pack.version_info.mc_ver) {
-            panic!("added incompatible mod version");
-        }
-    }
-
-    create_dir_all(config.pack_path.clone()).expect("create_dir_all");
-    let mut pack_fd = File::create(
-        config.pack_path.clone()
-            + "/"
-            + &pack.name.to_lowercase().as_str().replace(" ", "-")
-            + ".mtpck",
-    )
-    .expect("create");
-
-    write!(
-        &mut pack_fd,
-        "{}",
-        toml::to_string(&pack).expect("to_string")
-    )
-    .expect("write");
+        let game_versions = pack.add_mod(mc_mod, client)?;
+        // a pinned version is kept like in an existing pack, add_mod warns if it does not fit
+        let pinned = split_version(mc_mod).1.is_some();
+        if !pinned && !game_versions.contains(&pack.version_info.mc_ver) {
+            return Err(ApiError::no_compatible_version(mc_mod));
+        }
+    }
+
+    pack.save(config)?;
     println!(
         "Created Pack: {}, Minecraft-{}",
         pack.name, pack.version_info.mc_ver
     );
+    Ok(())
 }
 
-pub fn update_pack(client: &Client, name: String, config: &Configuration) -> Result<(), ApiError> {
-    let mut pack = Pack::open(&name, config);
-    println!("Updating mod entries in {name} Modpack.");
+/// Update every mod of a pack that is not pinned to its newest compatible version.
+///
+/// The changelogs of all versions a mod skipped are printed, and returned
+/// so they can be exported.
+fn update_entries(client: &ModrinthClient, pack: &mut Pack) -> Result<Changelog, ApiError> {
+    say!("Updating mod entries in {} Modpack.", pack.name);
+    let mut changelog = Changelog {
+        pack: pack.name.clone(),
+        mods: Vec::new(),
+    };
+    let mut updated: Vec<Version> = Vec::new();
+    let mut pinned: Vec<String> = Vec::new();
+    let mut external: Vec<String> = Vec::new();
+    let mut not_mods: Vec<String> = Vec::new();
     for (key, value) in pack.mods.clone() {
-        let mut mod_version: PackMod = value.try_into().expect("try_into");
-        let project_version = get_project_version(
-            client,
-            config.staging,
-            key.clone(),
-            pack.version_info.clone(),
-        )?;
+        let mod_version: PackMod = value.try_into()?;
+        if mod_version.external {
+            external.push(key);
+            continue;
+        }
+        // resource packs and shaders have no versions for the loader of the pack
+        if !mod_version.in_mods_folder() {
+            not_mods.push(key);
+            continue;
+        }
+        if mod_version.pinned && mod_version.version_id.is_empty() {
+            say!("Mod {} is pinned, but has no version id", mod_version.name);
+            pinned.push(key);
+            continue;
+        }
+        if mod_version.pinned {
+            // refresh the entry from the pinned version, the file url may have changed
+            let pinned_version = client.get_version(&mod_version.version_id)?;
+            say!(
+                "Mod {} is pinned to {}",
+                mod_version.name, pinned_version.version_number
+            );
+            let mut refreshed = PackMod::from_version(&pinned_version)?;
+            refreshed.pinned = true;
+            report_dropped(&key, refreshed.keep_extra_files(&mod_version, &pinned_version));
+            refreshed.keep_install_info(&mod_version);
+            pack.mods.insert(key.clone(), toml::Value::try_from(&refreshed)?);
+            pinned.push(key);
+            continue;
+        }
+        let versions = client.get_project_versions(&key, &pack.version_info)?;
+        let project_version = match versions.first() {
+            Some(v) => v.clone(),
+            None => return Err(ApiError::no_compatible_version(&key)),
+        };
         if mod_version.version_number != project_version.version_number {
-            println!(
+            say!(
                 "Found new version of {}\nOld: {}\nNew: {}",
                 mod_version.name, mod_version.version_number, project_version.version_number
             );
+            if let Some(mod_changelog) = ModChangelog::between(
+                &key,
+                &mod_version.version_id,
+                &mod_version.version_number,
+                &versions,
+            ) {
+                mod_changelog.print();
+                changelog.mods.push(mod_changelog);
+            }
             pack.mods.remove::<String>(&key.clone());
-            mod_version.name = project_version.name;
-            mod_version.verstion_type = project_version.version_type;
-            mod_version.version_number = project_version.version_number;
-            mod_version.file_url = project_version.files[0].url.clone();
-            mod_version.sha512 = project_version.files[0].hashes["sha512"]
-                .to_string()
-                .replace("\"", "");
-            mod_version.file_name = project_version.files[0].filename.clone();
-            pack.mods
-                .insert(key, toml::Value::try_from(&mod_version).expect("try_from"));
+            let mut new_version = PackMod::from_version(&project_version)?;
+            report_dropped(&key, new_version.keep_extra_files(&mod_version, &project_version));
+            new_version.keep_install_info(&mod_version);
+            pack.mods.insert(key, toml::Value::try_from(&new_version)?);
+            updated.push(project_version);
         } else {
-            println!("Mod {} is up to Date.", mod_version.name)
+            say!("Mod {} is up to Date.", mod_version.name)
         }
     }
-    let pack_name = pack.name.clone();
-    pack.save(config);
-    println!("To install the Updated mods, use '--pack install' for {pack_name}");
-    Ok(())
+    // new versions may depend on mods that are not in the pack yet
+    pack.add_dependencies(&updated, client)?;
+    pack.refresh_sides(client)?;
+    if !pinned.is_empty() {
+        say!("Not updated, pinned: {}", pinned.join(", "));
+    }
+    if !external.is_empty() {
+        say!("Not updated, not on modrinth: {}", external.join(", "));
+    }
+    if !not_mods.is_empty() {
+        say!("Not updated, not in the mods folder: {}", not_mods.join(", "));
+    }
+    Ok(changelog)
+}
+
+pub fn update_pack(client: &ModrinthClient, name: String, config: &Configuration) -> Result<Changelog, ApiError> {
+    let mut pack = Pack::open(&name, config)?;
+    let changelog = update_entries(client, &mut pack)?;
+    pack.save(config)?;
+    println!("To install the Updated mods, use '--pack install' for {}", pack.name);
+    Ok(changelog)
+}
+
+/// What updating the pack would change, the pack file is not written.
+pub fn plan_update(client: &ModrinthClient, name: String, config: &Configuration) -> Result<Plan, ApiError> {
+    let old = Pack::open(&name, config)?;
+    let mut pack = old.clone();
+    update_entries(client, &mut pack)?;
+    Plan::update(&old, &pack, config)
+}
+
+
+/// Create a pack from a folder of mod jars.
+///
+/// Every jar is identified by its sha512 hash, all hashes and then all their
+/// projects are resolved with a single request each, mods are keyed by the
+/// slug of their project. Of several jars of one project only the newest is
+/// added. Jars that modrinth does not know and the ones of older versions are
+/// listed at the end and are not part of the new pack.
+pub fn pack_from_folder(
+    client: &ModrinthClient,
+    name: String,
+    folder: &str,
+    config: &Configuration,
+) -> Result<Vec<String>, ApiError> {
+    let mut jars: Vec<(String, String)> = Vec::new();
+    for entry in read_dir(folder).map_err(|e| ApiError::from(e).at(folder))? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "jar") {
+            continue;
+        }
+        let mut jar = File::open(&path).map_err(|e| ApiError::from(e).at(path.display()))?;
+        let mut hasher = Sha512::new();
+        copy(&mut jar, &mut hasher).map_err(|e| ApiError::from(e).at(path.display()))?;
+        let hash = base16ct::lower::encode_string(&hasher.finalize());
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        jars.push((file_name, hash));
+    }
+    println!("Identifying {} jars in '{folder}'", jars.len());
+
+    let hashes: Vec<String> = jars.iter().map(|(_, hash)| hash.clone()).collect();
+    let identified = identify_files(client, &hashes)?;
+
+    let mut pack = Pack::new();
+    pack.name = name;
+    let mut unmatched: Vec<String> = Vec::new();
+    let mut displaced: Vec<String> = Vec::new();
+    let mut found: BTreeMap<String, (&String, &Version, &ApiFile, &Project)> = BTreeMap::new();
+    for (file_name, hash) in &jars {
+        let (version, file, project) = match identified.get(hash) {
+            Some(v) => v,
+            None => {
+                unmatched.push(file_name.clone());
+                continue;
+            }
+        };
+        // e.g. an old and a new jar of the same mod, both would get the key of the project
+        if let Some((other, other_version, _, _)) = found.get(&project.slug) {
+            if other_version.date_published >= version.date_published {
+                displaced.push(format!("{file_name} ({other} is newer)"));
+                continue;
+            }
+            displaced.push(format!("{other} ({file_name} is newer)"));
+        }
+        found.insert(project.slug.clone(), (file_name, version, file, project));
+    }
+    let mut matched: Vec<&Version> = Vec::new();
+    for (slug, (file_name, version, file, project)) in found {
+        let mut mod_version = PackMod::from_file(version, file);
+        mod_version.set_sides(project);
+        println!("Found '{}' for {file_name}", mod_version.name);
+        pack.mods.insert(slug, toml::Value::try_from(&mod_version)?);
+        matched.push(version);
+    }
+
+    // use the newest Minecraft version and a loader every identified mod supports
+    if let Some(first) = matched.first() {
+        let mut common: Vec<MCVersion> = first
+            .game_versions
+            .iter()
+            .filter(|ver| matched.iter().all(|v| v.game_versions.contains(ver)))
+            .cloned()
+            .collect();
+        common.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        pack.version_info.mc_ver = common.pop().unwrap_or(config.mc_ver.clone());
+        pack.version_info.loader = if matched.iter().all(|v| v.supports_loader(&config.loader)) {
+            config.loader
+        } else {
+            first
+                .loaders
+                .iter()
+                .filter_map(|name| LOADER::from_str(name).ok())
+                .find(|loader| matched.iter().all(|v| v.supports_loader(loader)))
+                .unwrap_or(config.loader)
+        };
+    }
+
+    pack.save(config)?;
+    println!(
+        "Created Pack: {}, Minecraft-{}, with {} mods",
+        pack.name,
+        pack.version_info.mc_ver,
+        pack.mods.len()
+    );
+    if !unmatched.is_empty() {
+        println!("The following jars could not be identified and were not added:");
+        for file_name in &unmatched {
+            println!("  {file_name}");
+        }
+    }
+    if !displaced.is_empty() {
+        println!("The following jars are older versions of mods in the pack and were not added:");
+        for file_name in &displaced {
+            println!("  {file_name}");
+        }
+    }
+    Ok(unmatched)
 }
 
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::util::testing::{serve_json, TempDir};
+
+    pub(super) const SODIUM_VERSION_ID: &str = "mc1.21.5-0.6.13";
+
+    /// sha512 hash of the sodium jar, only has to look like one
+    pub(super) fn sodium_sha512() -> String {
+        "5a".repeat(64)
+    }
+
+    pub(super) fn sodium_version() -> Value {
+        json!({
+            "id": SODIUM_VERSION_ID, "project_id": "AANobbMI", "game_versions": ["1.21.5"],
+            "loaders": ["fabric"], "name": "Sodium 0.6.13", "version_number": "mc1.21.5-0.6.13-fabric",
+            "downloads": 0, "version_type": "release", "dependencies": [],
+            "files": [{
+                "url": "https://cdn.modrinth.com/data/AANobbMI/versions/sodium-fabric-0.6.13.jar",
+                "filename": "sodium-fabric-0.6.13.jar",
+                "hashes": {"sha512": sodium_sha512(), "sha1": "1f".repeat(20)},
+                "size": 1000, "primary": true
+            }]
+        })
+    }
+
+    /// sha512 hash of a resource pack hosted on modrinth
+    pub(super) fn fresh_animations_sha512() -> String {
+        "f2".repeat(64)
+    }
+
+    /// resource packs have the loader "minecraft", which mapito does not know
+    pub(super) fn fresh_animations_version() -> Value {
+        json!({
+            "id": "FAv1", "project_id": "50dA9Sha", "game_versions": ["1.21.5"],
+            "loaders": ["minecraft"], "name": "Fresh Animations 1.9.4", "version_number": "1.9.4",
+            "downloads": 0, "version_type": "release", "dependencies": [],
+            "files": [{
+                "url": "https://cdn.modrinth.com/data/50dA9Sha/versions/FreshAnimations_v1.9.4.zip",
+                "filename": "FreshAnimations_v1.9.4.zip",
+                "hashes": {"sha512": fresh_animations_sha512(), "sha1": "f2".repeat(20)},
+                "size": 3000, "primary": true
+            }]
+        })
+    }
+
+    pub(super) fn fresh_animations_project() -> Value {
+        json!({
+            "id": "50dA9Sha", "slug": "fresh-animations", "project_type": "resourcepack", "team": "t",
+            "title": "Fresh Animations", "description": "", "published": "", "updated": "",
+            "license": {"id": "LicenseRef-All-Rights-Reserved", "name": "", "url": null}, "downloads": 0,
+            "game_versions": ["1.21.5"], "categories": [], "loaders": ["minecraft"],
+            "source_url": null, "client_side": "required", "server_side": "unsupported"
+        })
+    }
+
+    /// the api for the mods of [`round_trip_pack`]
+    pub(super) fn respond(path: &str) -> Value {
+        match path {
+            "/version_files" => json!({
+                sodium_sha512(): sodium_version(),
+                fresh_animations_sha512(): fresh_animations_version(),
+            }),
+            _ if path.starts_with("/projects?") => json!([sodium_project(), fresh_animations_project()]),
+            _ if path.starts_with("/versions?") => json!([sodium_version(), fresh_animations_version()]),
+            _ => json!(null),
+        }
+    }
+
+    pub(super) fn sodium_project() -> Value {
+        json!({
+            "id": "AANobbMI", "slug": "sodium", "project_type": "mod", "team": "t",
+            "title": "Sodium", "description": "", "published": "", "updated": "",
+            "license": {"id": "PolyForm-Shield-1.0.0", "name": "", "url": null}, "downloads": 0,
+            "game_versions": ["1.21.5"], "categories": [], "loaders": ["fabric"],
+            "source_url": null, "client_side": "required", "server_side": "unsupported"
+        })
+    }
+
+    /// A pack with a modrinth mod, a modrinth resource pack, a resource pack
+    /// modrinth does not know and an override, everything an export needs is
+    /// filled in.
+    pub(super) fn round_trip_pack(dir: &Path) -> Pack {
+        let mut pack = Pack::new();
+        pack.name = "Round Trip".to_string();
+        pack.version = Some("2.5".to_string());
+        pack.version_info.mc_ver = MCVersion::from_str("1.21.5").unwrap();
+        pack.version_info.loader = LOADER::FABRIC;
+        pack.loader_version = Some("0.16.14".to_string());
+
+        let version: Version = serde_json::from_value(sodium_version()).unwrap();
+        let project: Project = serde_json::from_value(sodium_project()).unwrap();
+        let mut sodium = PackMod::from_file(&version, &version.files[0]);
+        sodium.set_sides(&project);
+        pack.mods.insert(
+            "sodium".to_string(),
+            toml::Value::try_from(&sodium).unwrap(),
+        );
+
+        let version: Version = serde_json::from_value(fresh_animations_version()).unwrap();
+        let project: Project = serde_json::from_value(fresh_animations_project()).unwrap();
+        let mut fresh_animations = PackMod::from_file(&version, &version.files[0]);
+        fresh_animations.set_sides(&project);
+        fresh_animations.folder = Some(RESOURCEPACKS_FOLDER.to_string());
+        pack.mods.insert(
+            "fresh-animations".to_string(),
+            toml::Value::try_from(&fresh_animations).unwrap(),
+        );
+
+        let faithful = PackMod {
+            name: "faithful".to_string(),
+            verstion_type: VT::RELEASE,
+            version_number: String::new(),
+            file_url: "https://example.com/faithful.zip".to_string(),
+            file_name: "faithful.zip".to_string(),
+            sha512: "fa".repeat(64),
+            sha1: "fa".repeat(20),
+            size: 2000,
+            project_id: String::new(),
+            version_id: String::new(),
+            pinned: false,
+            extra_files: Vec::new(),
+            client_side: SideSupport::UNKNOWN,
+            server_side: SideSupport::UNKNOWN,
+            folder: Some(RESOURCEPACKS_FOLDER.to_string()),
+            external: true,
+        };
+        pack.mods.insert(
+            "faithful".to_string(),
+            toml::Value::try_from(&faithful).unwrap(),
+        );
+
+        let overrides = dir.join("source-overrides");
+        std::fs::create_dir_all(overrides.join("config")).unwrap();
+        std::fs::write(overrides.join("config/sodium-options.json"), "{}").unwrap();
+        pack.overrides = Some(overrides.to_string_lossy().to_string());
+        pack
+    }
+
+    #[test]
+    fn update_skips_files_outside_the_mods_folder() {
+        let dir = TempDir::new("update-test");
+        let mut pack = round_trip_pack(&dir);
+        pack.mods.remove("sodium");
+        let old_mods = pack.mods.clone();
+        // only the sides are refreshed, asking for versions of the resource packs would fail
+        let client = ModrinthClient::new(&serve_json(1, respond));
+
+        let changelog = update_entries(&client, &mut pack).unwrap();
+
+        assert!(changelog.is_empty());
+        assert_eq!(pack.mods, old_mods);
+    }
+
+    fn jar_hash(content: &str) -> String {
+        base16ct::lower::encode_string(&Sha512::digest(content.as_bytes()))
+    }
+
+    /// two versions of fabric api, for jars with their version number as content
+    fn respond_fabric_api(path: &str) -> Value {
+        let version = |id: &str, date: &str| {
+            json!({
+                "id": id, "project_id": "P7dR8mSH", "game_versions": ["1.21.5"],
+                "loaders": ["fabric"], "name": id, "version_number": id, "downloads": 0,
+                "version_type": "release", "date_published": date, "dependencies": [],
+                "files": [{
+                    "url": format!("https://cdn.modrinth.com/fabric-api-{id}.jar"),
+                    "filename": format!("fabric-api-{id}.jar"),
+                    "hashes": {"sha512": jar_hash(id)}, "size": id.len(), "primary": true
+                }]
+            })
+        };
+        match path {
+            "/version_files" => json!({
+                jar_hash("0.118.0"): version("0.118.0", "2025-03-01T00:00:00Z"),
+                jar_hash("0.117.0"): version("0.117.0", "2025-02-01T00:00:00Z"),
+            }),
+            _ if path.starts_with("/projects?") => json!([{
+                "id": "P7dR8mSH", "slug": "fabric-api", "project_type": "mod", "team": "t",
+                "title": "Fabric API", "description": "", "published": "", "updated": "",
+                "license": {"id": "Apache-2.0", "name": "", "url": null}, "downloads": 0,
+                "game_versions": ["1.21.5"], "categories": [], "loaders": ["fabric"],
+                "source_url": null
+            }]),
+            _ => json!(null),
+        }
+    }
+
+    #[test]
+    fn pack_from_folder_keeps_the_newest_jar_of_a_project() {
+        let dir = TempDir::new("folder-test");
+        let mods = dir.join("mods");
+        std::fs::create_dir_all(&mods).unwrap();
+        std::fs::write(mods.join("fabric-api-0.117.0.jar"), "0.117.0").unwrap();
+        std::fs::write(mods.join("fabric-api-0.118.0.jar"), "0.118.0").unwrap();
+        std::fs::write(mods.join("own-mod.jar"), "own").unwrap();
+        let client = ModrinthClient::new(&serve_json(2, respond_fabric_api));
+        let config = Configuration {
+            pack_path: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let unmatched =
+            pack_from_folder(&client, "folder".to_string(), &mods.to_string_lossy(), &config).unwrap();
+
+        assert_eq!(unmatched, ["own-mod.jar"]);
+        let pack = Pack::open("folder", &config).unwrap();
+        assert_eq!(pack.mods.len(), 1);
+        assert_eq!(pack.mods["fabric-api"]["version_number"].as_str(), Some("0.118.0"));
+    }
+
+    #[test]
+    fn split_version_without_version() {
+        assert_eq!(split_version("sodium"), ("sodium", None));
+    }
+
+    #[test]
+    fn split_version_with_version() {
+        assert_eq!(split_version("sodium@mc1.21.5-0.6.13"), ("sodium", Some("mc1.21.5-0.6.13")));
+        assert_eq!(split_version("sodium@AANobbMI"), ("sodium", Some("AANobbMI")));
+    }
+
+    #[test]
+    fn split_version_with_empty_version() {
+        assert_eq!(split_version("sodium@"), ("sodium", None));
+    }
+
+    #[test]
+    fn split_version_splits_at_the_first_at() {
+        assert_eq!(split_version("sodium@1.0@beta"), ("sodium", Some("1.0@beta")));
+    }
+}
@@ -0,0 +1,565 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, File},
+    io::{copy, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zip::{
+    write::{FileOptions, SimpleFileOptions},
+    CompressionMethod, ZipArchive, ZipWriter,
+};
+
+use crate::{
+    config::Configuration,
+    mc_info::{MCVersion, LOADER, VT},
+    mrapi::{client::ModrinthClient, defines::SideSupport},
+    pack::{
+        file_hash, identify_files, pack::Pack, split_instance_path, ExtraFile, IdentifiedFiles,
+        PackMod,
+    },
+    util::error::ApiError,
+};
+
+pub const INDEX_FILE: &str = "modrinth.index.json";
+pub const OVERRIDES: &str = "overrides";
+pub const CLIENT_OVERRIDES: &str = "client-overrides";
+pub const SERVER_OVERRIDES: &str = "server-overrides";
+pub const FORMAT_VERSION: u32 = 1;
+//loader meta APIs, used to find a loader version if the pack does not specify one
+pub const FABRIC_META: &str = "https://meta.fabricmc.net/v2/versions/loader/";
+pub const QUILT_META: &str = "https://meta.quiltmc.org/v3/versions/loader/";
+
+/// The modrinth.index.json at the root of every .mrpack file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MrIndex {
+    pub format_version: u32,
+    pub game: String,
+    pub version_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    pub files: Vec<MrFile>,
+    pub dependencies: BTreeMap<String, String>,
+}
+
+/// A single downloadable file listed in the index
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MrFile {
+    pub path: String,
+    pub hashes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+    pub downloads: Vec<String>,
+    pub file_size: u64,
+}
+
+/// Read a .mrpack file and turn it into a Pack.
+///
+/// The files are identified by their hashes, so the pack gets the real
+/// versions and slugs. The overrides folders of the modpack are extracted
+/// next to the pack file, so that `Pack::install` can copy them into the
+/// instance later on.
+pub fn import_mrpack(
+    path: &str,
+    client: &ModrinthClient,
+    config: &Configuration,
+) -> Result<Pack, ApiError> {
+    let mrpack_fd = File::open(path).map_err(|e| ApiError::from(e).at(path))?;
+    let mut archive = ZipArchive::new(mrpack_fd)?;
+
+    let mut body = String::new();
+    archive.by_name(INDEX_FILE)?.read_to_string(&mut body)?;
+    let index: MrIndex = serde_json::from_str(&body)?;
+
+    if index.game != "minecraft" {
+        return Err(ApiError::invalid_data(format!(
+            "Unsupported game '{}'",
+            index.game
+        )));
+    }
+
+    let mut pack = Pack::new();
+    pack.name = index.name.clone();
+    pack.version = Some(index.version_id.clone());
+    for (dependency, version) in &index.dependencies {
+        match dependency.as_str() {
+            "minecraft" => {
+                pack.version_info.mc_ver =
+                    MCVersion::from_str(version).map_err(ApiError::invalid_data)?
+            }
+            other => match loader_from_dependency(other) {
+                Some(loader) => {
+                    pack.version_info.loader = loader;
+                    pack.loader_version = Some(version.clone());
+                }
+                None => println!("Warning: unknown dependency '{dependency}' in {INDEX_FILE}."),
+            },
+        }
+    }
+    if !index.dependencies.contains_key("minecraft") {
+        return Err(ApiError::invalid_data(
+            "Modpack does not specify a Minecraft version",
+        ));
+    }
+
+    let hashes: Vec<String> = index
+        .files
+        .iter()
+        .filter_map(|file| file.hashes.get("sha512").cloned())
+        .collect();
+    let identified = identify_files(client, &hashes)?;
+    let mut entries = Vec::new();
+    for file in &index.files {
+        entries.push(file_to_pack_mod(file, &identified)?);
+    }
+    // the primary file of a version becomes the entry, its other files become extra files of it
+    entries.sort_by_key(|(_, pack_mod)| !is_primary_file(pack_mod, &identified));
+    for (key, pack_mod) in entries {
+        let existing: Option<PackMod> = match pack.mods.get(&key) {
+            Some(value) => Some(value.clone().try_into()?),
+            None => None,
+        };
+        let (key, pack_mod) = match existing {
+            Some(mut existing)
+                if !existing.external && existing.version_id == pack_mod.version_id =>
+            {
+                existing
+                    .extra_files
+                    .push(extra_file(&pack_mod, &identified));
+                (key, existing)
+            }
+            Some(_) => (key + "-" + &pack_mod.name, pack_mod),
+            None => (key, pack_mod),
+        };
+        pack.mods.insert(key, toml::Value::try_from(&pack_mod)?);
+    }
+
+    pack.overrides = extract_overrides(
+        &mut archive,
+        OVERRIDES,
+        &overrides_path(&pack, config, OVERRIDES),
+    )?;
+    pack.client_overrides = extract_overrides(
+        &mut archive,
+        CLIENT_OVERRIDES,
+        &overrides_path(&pack, config, CLIENT_OVERRIDES),
+    )?;
+    pack.server_overrides = extract_overrides(
+        &mut archive,
+        SERVER_OVERRIDES,
+        &overrides_path(&pack, config, SERVER_OVERRIDES),
+    )?;
+
+    println!(
+        "Imported {} mods from {}, Minecraft-{}",
+        pack.mods.len(),
+        index.name,
+        pack.version_info.mc_ver
+    );
+    Ok(pack)
+}
+
+/// Copy the overrides of a pack into the instance folder.
+///
+/// Overrides are relative to the instance root, which is the parent of the
+/// mod folder the pack gets installed to.
+pub fn install_overrides(overrides: &str, install_path: &str) -> Result<(), ApiError> {
+    let instance = match Path::new(install_path).parent() {
+        Some(p) => p,
+        None => {
+            return Err(ApiError::invalid_data(format!(
+                "'{install_path}' has no parent folder"
+            )))
+        }
+    };
+    println!("Copying overrides to '{}'", instance.display());
+    copy_dir_all(Path::new(overrides), instance).map_err(|e| ApiError::from(e).at(overrides))
+}
+
+/// Write the given pack as a .mrpack file to `path`.
+///
+/// Mods that were added before hashes and sizes were stored in the pack
+/// are downloaded once to compute them.
+pub fn export_mrpack(pack: &Pack, path: &str, client: &Client) -> Result<(), ApiError> {
+    if pack.version_info.mc_ver.is_latest() {
+        return Err(ApiError::invalid_data(
+            "Cannot export a pack without a fixed Minecraft version",
+        ));
+    }
+    let version = match &pack.version {
+        Some(v) => v.clone(),
+        None => {
+            return Err(ApiError::invalid_data(
+                "Cannot export a pack without a version",
+            ))
+        }
+    };
+    let loader_version = match &pack.loader_version {
+        Some(v) => v.clone(),
+        None => latest_loader_version(client, pack)?,
+    };
+
+    let mut dependencies = BTreeMap::new();
+    dependencies.insert(
+        "minecraft".to_string(),
+        pack.version_info.mc_ver.to_string(),
+    );
+    dependencies.insert(
+        loader_dependency(&pack.version_info.loader).to_string(),
+        loader_version,
+    );
+
+    let mut files: Vec<MrFile> = Vec::new();
+    for (key, value) in &pack.mods {
+        let mut pack_mod: PackMod = value.clone().try_into()?;
+        if pack_mod.sha1.is_empty() || pack_mod.size == 0 {
+            println!("Computing hashes for '{key}'");
+            pack_mod.fetch_file_info(client)?;
+        }
+        let env = mod_env(&pack_mod);
+        let path = pack_mod.instance_path();
+        let mut hashes = BTreeMap::new();
+        hashes.insert("sha1".to_string(), pack_mod.sha1);
+        hashes.insert("sha512".to_string(), pack_mod.sha512);
+        files.push(MrFile {
+            path,
+            hashes,
+            env: env.clone(),
+            downloads: vec![pack_mod.file_url],
+            file_size: pack_mod.size,
+        });
+        for extra in pack_mod.extra_files {
+            let path = extra.instance_path(pack_mod.folder.as_deref());
+            let mut hashes = BTreeMap::new();
+            hashes.insert("sha1".to_string(), extra.sha1);
+            hashes.insert("sha512".to_string(), extra.sha512);
+            files.push(MrFile {
+                path,
+                hashes,
+                env: env.clone(),
+                downloads: vec![extra.file_url],
+                file_size: extra.size,
+            });
+        }
+    }
+
+    let index = MrIndex {
+        format_version: FORMAT_VERSION,
+        game: "minecraft".to_string(),
+        version_id: version,
+        name: pack.name.clone(),
+        summary: None,
+        files,
+        dependencies,
+    };
+
+    let mrpack_fd = File::create(path).map_err(|e| ApiError::from(e).at(path))?;
+    let mut archive = ZipWriter::new(mrpack_fd);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    archive.start_file(INDEX_FILE, options)?;
+    archive.write_all(serde_json::to_string_pretty(&index)?.as_bytes())?;
+    for (overrides, prefix) in [
+        (&pack.overrides, OVERRIDES),
+        (&pack.client_overrides, CLIENT_OVERRIDES),
+        (&pack.server_overrides, SERVER_OVERRIDES),
+    ] {
+        if let Some(overrides) = overrides {
+            add_dir_to_zip(&mut archive, Path::new(overrides), prefix, options)?;
+        }
+    }
+    archive.finish()?;
+
+    println!("Exported {} to '{path}'", pack.name);
+    Ok(())
+}
+
+/// where the overrides of the given kind, e.g. [`OVERRIDES`], of the given pack are stored
+pub fn overrides_path(pack: &Pack, config: &Configuration, kind: &str) -> String {
+    config.pack_path.clone() + "/" + &pack.name.to_lowercase().replace(" ", "-") + "-" + kind
+}
+
+/// Map a file of the index to a PackMod, together with the key it is stored under.
+///
+/// Files modrinth knows by their hash are keyed by the slug of their project,
+/// so that `update_pack` can look them up. Everything else is keyed by its
+/// file name and marked external, it is installed but never updated.
+fn file_to_pack_mod(
+    file: &MrFile,
+    identified: &IdentifiedFiles,
+) -> Result<(String, PackMod), ApiError> {
+    let file_url = match file.downloads.first() {
+        Some(url) => url.clone(),
+        None => {
+            return Err(ApiError::invalid_data(format!(
+                "No download for '{}'",
+                file.path
+            )))
+        }
+    };
+    let sha512 = match file.hashes.get("sha512") {
+        Some(hash) => hash.clone(),
+        None => {
+            return Err(ApiError::invalid_data(format!(
+                "No sha512 hash for '{}'",
+                file.path
+            )))
+        }
+    };
+    let (folder, file_name) = split_instance_path(&file.path)?;
+
+    let (key, mut pack_mod) = match identified.get(&sha512) {
+        Some((version, api_file, project)) => {
+            let mut pack_mod = PackMod::from_file(version, api_file);
+            pack_mod.set_sides(project);
+            (project.slug.clone(), pack_mod)
+        }
+        None => {
+            let stem = file_name
+                .rsplit_once('.')
+                .map_or(file_name.as_str(), |(stem, _)| stem)
+                .to_string();
+            let pack_mod = PackMod {
+                name: stem.clone(),
+                verstion_type: VT::RELEASE,
+                version_number: String::new(),
+                file_url: String::new(),
+                file_name: String::new(),
+                sha512: String::new(),
+                sha1: String::new(),
+                size: 0,
+                project_id: String::new(),
+                version_id: String::new(),
+                pinned: false,
+                extra_files: Vec::new(),
+                client_side: SideSupport::UNKNOWN,
+                server_side: SideSupport::UNKNOWN,
+                folder: None,
+                external: true,
+            };
+            (stem, pack_mod)
+        }
+    };
+    // the index is what the pack author chose, it wins over the api
+    pack_mod.file_url = file_url;
+    pack_mod.file_name = file_name;
+    pack_mod.sha512 = sha512;
+    pack_mod.sha1 = file.hashes.get("sha1").cloned().unwrap_or_default();
+    pack_mod.size = file.file_size;
+    pack_mod.folder = folder;
+    if file.env.is_some() {
+        pack_mod.client_side = env_side(file, "client");
+        pack_mod.server_side = env_side(file, "server");
+    }
+    Ok((key, pack_mod))
+}
+
+/// true if the file of the entry is the primary file of its version
+fn is_primary_file(pack_mod: &PackMod, identified: &IdentifiedFiles) -> bool {
+    identified
+        .get(&pack_mod.sha512)
+        .and_then(|(version, _, _)| version.primary_file().ok())
+        .is_some_and(|file| file_hash(file, "sha512") == pack_mod.sha512)
+}
+
+/// the entry of a non primary file of a version, as extra file of the entry of the version
+fn extra_file(pack_mod: &PackMod, identified: &IdentifiedFiles) -> ExtraFile {
+    let file_type = identified
+        .get(&pack_mod.sha512)
+        .and_then(|(_, file, _)| file.file_type.clone());
+    ExtraFile {
+        file_name: pack_mod.file_name.clone(),
+        file_url: pack_mod.file_url.clone(),
+        sha512: pack_mod.sha512.clone(),
+        sha1: pack_mod.sha1.clone(),
+        size: pack_mod.size,
+        file_type,
+    }
+}
+
+/// the env of a mod in the index, only written if both sides are known
+fn mod_env(pack_mod: &PackMod) -> Option<BTreeMap<String, String>> {
+    if pack_mod.client_side.is_unknown() || pack_mod.server_side.is_unknown() {
+        return None;
+    }
+    let mut env = BTreeMap::new();
+    env.insert("client".to_string(), pack_mod.client_side.to_string());
+    env.insert("server".to_string(), pack_mod.server_side.to_string());
+    Some(env)
+}
+
+/// the support of a file for the given side, unknown if the index does not say
+fn env_side(file: &MrFile, side: &str) -> SideSupport {
+    file.env
+        .as_ref()
+        .and_then(|env| env.get(side))
+        .and_then(|value| SideSupport::from_str(value).ok())
+        .unwrap_or_default()
+}
+
+/// the name of the index dependency for the given loader
+pub fn loader_dependency(loader: &LOADER) -> &'static str {
+    match loader {
+        LOADER::FABRIC => "fabric-loader",
+        LOADER::QUILT => "quilt-loader",
+        LOADER::FORGE => "forge",
+        LOADER::NEOFORGE => "neoforge",
+    }
+}
+
+fn loader_from_dependency(dependency: &str) -> Option<LOADER> {
+    match dependency {
+        "fabric-loader" => Some(LOADER::FABRIC),
+        "quilt-loader" => Some(LOADER::QUILT),
+        "forge" => Some(LOADER::FORGE),
+        "neoforge" => Some(LOADER::NEOFORGE),
+        _ => None,
+    }
+}
+
+/// ask the loader meta API for the newest stable loader of the packs Minecraft version
+pub fn latest_loader_version(client: &Client, pack: &Pack) -> Result<String, ApiError> {
+    let meta = match pack.version_info.loader {
+        LOADER::FABRIC => FABRIC_META,
+        LOADER::QUILT => QUILT_META,
+        loader => {
+            return Err(ApiError::invalid_data(format!(
+                "No {loader} version known, set 'loader_version' in the pack file"
+            )))
+        }
+    };
+    let versions: Vec<Value> = client
+        .get(meta.to_string() + &pack.version_info.mc_ver.to_string())
+        .send()?
+        .json()?;
+    versions
+        .iter()
+        .map(|v| &v["loader"])
+        .find(|loader| loader["stable"].as_bool().unwrap_or(true))
+        .and_then(|loader| loader["version"].as_str())
+        .map(|v| v.to_string())
+        .ok_or(ApiError::invalid_data(format!(
+            "No {} version found for Minecraft-{}",
+            pack.version_info.loader, pack.version_info.mc_ver
+        )))
+}
+
+/// extract everything below the folder `prefix` into `dest`, returns `dest` if there was anything to extract
+fn extract_overrides(
+    archive: &mut ZipArchive<File>,
+    prefix: &str,
+    dest: &str,
+) -> Result<Option<String>, ApiError> {
+    let prefix = Path::new(prefix);
+    let mut extracted = false;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let rel_path = match entry.enclosed_name() {
+            Some(p) => match p.strip_prefix(prefix) {
+                Ok(rel) if !rel.as_os_str().is_empty() => rel.to_path_buf(),
+                _ => continue,
+            },
+            None => continue,
+        };
+        let out_path = Path::new(dest).join(rel_path);
+        if entry.is_dir() {
+            create_dir_all(&out_path)?;
+            continue;
+        }
+        if let Some(parent) = out_path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut out_fd = File::create(&out_path)?;
+        copy(&mut entry, &mut out_fd)?;
+        extracted = true;
+    }
+    Ok(extracted.then(|| dest.to_string()))
+}
+
+fn copy_dir_all(src: &Path, dest: &Path) -> std::io::Result<()> {
+    create_dir_all(dest)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// add the contents of `src` to the archive, below the folder `prefix`
+fn add_dir_to_zip(
+    archive: &mut ZipWriter<File>,
+    src: &Path,
+    prefix: &str,
+    options: FileOptions<()>,
+) -> Result<(), ApiError> {
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let name = prefix.to_string() + "/" + &entry.file_name().to_string_lossy();
+        if entry.file_type()?.is_dir() {
+            archive.add_directory(name.as_str(), options)?;
+            add_dir_to_zip(archive, &entry.path(), &name, options)?;
+        } else {
+            archive.start_file(name.as_str(), options)?;
+            let mut file = File::open(entry.path())?;
+            copy(&mut file, archive)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::tests::{respond, round_trip_pack};
+    use crate::util::testing::{serve_json, TempDir};
+
+    #[test]
+    fn mrpack_round_trip() {
+        let dir = TempDir::new("mrpack-test");
+        let pack = round_trip_pack(&dir);
+        let path = dir.join("round-trip.mrpack").to_string_lossy().to_string();
+        export_mrpack(&pack, &path, &Client::new()).unwrap();
+
+        let client = ModrinthClient::new(&serve_json(2, respond));
+        let config = Configuration {
+            pack_path: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let imported = import_mrpack(&path, &client, &config).unwrap();
+
+        assert_eq!(imported.name, pack.name);
+        assert_eq!(imported.version, pack.version);
+        assert_eq!(imported.version_info.mc_ver, pack.version_info.mc_ver);
+        assert_eq!(imported.version_info.loader, pack.version_info.loader);
+        assert_eq!(imported.loader_version, pack.loader_version);
+        assert_eq!(imported.mods, pack.mods);
+        let overrides = imported.overrides.unwrap();
+        assert_eq!(overrides, overrides_path(&pack, &config, OVERRIDES));
+        assert_eq!(
+            std::fs::read_to_string(Path::new(&overrides).join("config/sodium-options.json"))
+                .unwrap(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn export_needs_a_pack_version() {
+        let dir = TempDir::new("mrpack-test-version");
+        let mut pack = round_trip_pack(&dir);
+        pack.version = None;
+        let path = dir.join("round-trip.mrpack").to_string_lossy().to_string();
+        let err = export_mrpack(&pack, &path, &Client::new()).unwrap_err();
+        assert!(err.to_string().contains("without a version"));
+    }
+}
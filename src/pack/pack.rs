@@ -1,20 +1,37 @@
 use std::{
+    collections::{BTreeMap, HashSet},
     fmt::Display,
-    fs::{create_dir_all, remove_file, File},
-    io::{Read, Write},
+    fs::{create_dir_all, read_dir, read_to_string, remove_file, File},
+    path::Path,
+    io::{ErrorKind, Read, Write},
     str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
 };
 
-use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use toml::Table;
 
 use crate::{
-    client::Downloader,
+    client::{Downloader, FileDownload},
     config::Configuration,
     mc_info::{MCVersion, MVDescriptor, LOADER, VT},
-    mrapi::interactions::{get_project_info, get_project_version},
-    pack::PackMod,
+    mrapi::{
+        client::ModrinthClient,
+        defines::{Project, Version},
+    },
+    pack::{
+        manifest::{manifest_name, retire_file, InstallManifest, StaleAction},
+        mrpack::install_overrides,
+        split_version,
+        store::JarStore,
+        PackMod,
+    },
+    say,
+    util::{error::ApiError, progress::Progress},
 };
 
 #[derive(Debug, Clone)]
@@ -24,6 +41,9 @@ pub enum PackAction {
     MODIFY,
     INSTALL,
     REMOVE,
+    IMPORT,
+    EXPORT,
+    SCAN,
 }
 
 impl Display for PackAction {
@@ -34,6 +54,9 @@ impl Display for PackAction {
             PackAction::MODIFY => "modify",
             PackAction::INSTALL => "install",
             PackAction::REMOVE => "remove",
+            PackAction::IMPORT => "import",
+            PackAction::EXPORT => "export",
+            PackAction::SCAN => "scan",
         };
         write!(f, "{}", to_display)
     }
@@ -49,16 +72,78 @@ impl FromStr for PackAction {
             "modify" => Ok(Self::MODIFY),
             "install" => Ok(Self::INSTALL),
             "remove" => Ok(Self::REMOVE),
+            "import" => Ok(Self::IMPORT),
+            "export" => Ok(Self::EXPORT),
+            "scan" => Ok(Self::SCAN),
             _ => Err("Invalid input".to_string()),
         }
     }
 }
 
+/// The side a pack is installed for, mods that do not support it are skipped.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum InstallTarget {
+    CLIENT,
+    SERVER,
+    /// every mod is installed
+    BOTH,
+}
+
+impl Display for InstallTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            Self::CLIENT => "client",
+            Self::SERVER => "server",
+            Self::BOTH => "both",
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl FromStr for InstallTarget {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "client" => Ok(Self::CLIENT),
+            "server" => Ok(Self::SERVER),
+            "both" => Ok(Self::BOTH),
+            _ => Err("invalid install target, use one of: client, server, both".to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for InstallTarget {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match Self::from_str(&String::deserialize(deserializer)?) {
+            Ok(target) => Ok(target),
+            Err(e) => Err(serde::de::Error::custom(e)),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Pack {
     pub name: String,
+    /// version of the modpack itself, written to exported modpacks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     pub version_info: MVDescriptor,
     pub mods: Table,
+    /// folder with files that are copied into the instance on install
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overrides: Option<String>,
+    /// overrides only copied into client instances, `--target client` or `both`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_overrides: Option<String>,
+    /// overrides only copied into servers, `--target server`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_overrides: Option<String>,
+    /// exact version of the modloader, needed when exporting to .mrpack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loader_version: Option<String>,
 }
 
 impl Pack {
@@ -66,36 +151,119 @@ impl Pack {
     pub fn new() -> Self {
         Pack {
             name: "".to_string(),
+            version: None,
             version_info: MVDescriptor {
                 mc_ver: MCVersion::new(),
                 version_types: vec![VT::RELEASE, VT::BETA, VT::ALPHA],
                 loader: LOADER::FABRIC,
             },
             mods: Table::new(),
+            overrides: None,
+            client_overrides: None,
+            server_overrides: None,
+            loader_version: None,
         }
     }
 
     /// open the pack file for the given modpack and return Pack object
-    pub fn open(name: &String, config: &Configuration) -> Self {
-        let mut pack_file = File::open(
-            config.pack_path.clone()
-                + "/"
-                + name
-                    .clone()
-                    .to_lowercase()
-                    .as_str()
-                    .replace(" ", "-")
-                    .as_str()
-                + ".mtpck",
-        )
-        .expect("open");
+    pub fn open(name: &str, config: &Configuration) -> Result<Self, ApiError> {
+        let path = pack_file_path(name, config);
+        let mut pack_file = File::open(&path).map_err(|e| ApiError::from(e).at(&path))?;
         let mut body = String::new();
 
-        pack_file.read_to_string(&mut body).expect("read_to_string");
+        pack_file
+            .read_to_string(&mut body)
+            .map_err(|e| ApiError::from(e).at(&path))?;
+
+        toml::from_str::<Pack>(&body).map_err(|e| ApiError::from(e).at(&path))
+    }
+
+    /// Open every pack in the pack path.
+    ///
+    /// Fails if any of them cannot be read, so nothing relies on an incomplete list.
+    pub fn open_all(config: &Configuration) -> Result<Vec<Self>, ApiError> {
+        let entries = match read_dir(&config.pack_path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ApiError::from(e).at(&config.pack_path)),
+        };
+        let mut packs = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| ApiError::from(e).at(&config.pack_path))?.path();
+            if path.extension().is_none_or(|ext| ext != "mtpck") {
+                continue;
+            }
+            let body = read_to_string(&path).map_err(|e| ApiError::from(e).at(path.display()))?;
+            packs.push(toml::from_str(&body).map_err(|e| ApiError::from(e).at(path.display()))?);
+        }
+        Ok(packs)
+    }
 
-        let pack = toml::from_str::<Pack>(&body).expect("from_string");
+    /// the sha512 hashes of every file the pack installs
+    pub fn file_hashes(&self) -> Result<Vec<String>, ApiError> {
+        let mut hashes = Vec::new();
+        for value in self.mods.values() {
+            let mod_version: PackMod = value.clone().try_into()?;
+            hashes.extend(mod_version.downloads("").into_iter().map(|d| d.sha512));
+        }
+        Ok(hashes)
+    }
 
-        pack
+    /// every file the pack installs into `dir` for the target side, with the key of its mod
+    pub fn downloads(
+        &self,
+        dir: &str,
+        target: InstallTarget,
+    ) -> Result<Vec<(&String, FileDownload)>, ApiError> {
+        let mut downloads = Vec::new();
+        for (key, value) in &self.mods {
+            let mod_version: PackMod = value.clone().try_into()?;
+            if !mod_version.supports(target) {
+                continue;
+            }
+            for download in mod_version.downloads(dir) {
+                downloads.push((key, download));
+            }
+        }
+        Ok(downloads)
+    }
+
+    /// the keys of the mods that do not support the target side
+    pub fn skipped_mods(&self, target: InstallTarget) -> Result<Vec<&String>, ApiError> {
+        let mut skipped = Vec::new();
+        for (key, value) in &self.mods {
+            let mod_version: PackMod = value.clone().try_into()?;
+            if !mod_version.supports(target) {
+                skipped.push(key);
+            }
+        }
+        Ok(skipped)
+    }
+
+    /// Record the client and server support of every mod from its project.
+    ///
+    /// All projects are looked up with a single request, mods without a
+    /// project id keep what they have.
+    pub fn refresh_sides(&mut self, client: &ModrinthClient) -> Result<(), ApiError> {
+        let mut mods: Vec<(String, PackMod)> = Vec::new();
+        for (key, value) in &self.mods {
+            let mod_version: PackMod = value.clone().try_into()?;
+            if !mod_version.project_id.is_empty() {
+                mods.push((key.clone(), mod_version));
+            }
+        }
+        if mods.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<String> = mods.iter().map(|(_, m)| m.project_id.clone()).collect();
+        let projects = client.get_projects(&ids)?;
+        for (key, mut mod_version) in mods {
+            if let Some(project) = projects.iter().find(|p| p.id == mod_version.project_id) {
+                mod_version.set_sides(project);
+                self.mods.insert(key, toml::Value::try_from(&mod_version)?);
+            }
+        }
+        Ok(())
     }
 
     /// Print all mods contained in the Pack
@@ -107,83 +275,399 @@ impl Pack {
     }
 
     /// write this pack to File, at the path given in the config
-    pub fn save(&self, config: &Configuration) {
+    pub fn save(&self, config: &Configuration) -> Result<(), ApiError> {
         println!("Saving Changes for {}", self.name);
-        create_dir_all(config.pack_path.clone()).expect("create_dir_all");
-        let mut pack_fd = File::create(
-            config.pack_path.clone()
-                + "/"
-                + &self.name.to_lowercase().as_str().replace(" ", "-")
-                + ".mtpck",
-        )
-        .expect("create");
+        let path = pack_file_path(&self.name, config);
+        create_dir_all(config.pack_path.clone())
+            .map_err(|e| ApiError::from(e).at(&config.pack_path))?;
+        let mut pack_fd = File::create(&path).map_err(|e| ApiError::from(e).at(&path))?;
 
-        write!(
-            &mut pack_fd,
-            "{}",
-            toml::to_string(self).expect("to_string")
-        )
-        .expect("write");
+        write!(&mut pack_fd, "{}", toml::to_string(self)?).map_err(|e| ApiError::from(e).at(&path))
     }
 
     /// remove pack from file system
-    pub fn remove(&self, config: &Configuration) {
-        remove_file(
-            config.pack_path.clone()
-                + "/"
-                + &self.name.to_lowercase().as_str().replace(" ", "-")
-                + ".mtpck",
-        )
-        .expect("remove_file");
+    pub fn remove(&self, config: &Configuration) -> Result<(), ApiError> {
+        let path = pack_file_path(&self.name, config);
+        remove_file(&path).map_err(|e| ApiError::from(e).at(&path))
     }
 
-    /// adds a mod and its dependencies
-    pub fn add_mod(&mut self, mod_slug: &String, client: &Client, staging: usize) -> Vec<MCVersion> {
+    /// Adds a mod and its dependencies.
+    ///
+    /// `slug@version` adds the given version id or number and pins the mod to it.
+    pub fn add_mod(
+        &mut self,
+        mod_slug: &str,
+        client: &ModrinthClient,
+    ) -> Result<Vec<MCVersion>, ApiError> {
+        let (mod_slug, pin) = split_version(mod_slug);
         println!("Looking for {mod_slug}");
-        let project_version =
-            get_project_version(client, staging, mod_slug.clone(), self.version_info.clone())
-                .expect("get_project_version");
-        let mod_version = PackMod {
-            name: project_version.name,
-            verstion_type: project_version.version_type,
-            version_number: project_version.version_number,
-            file_url: project_version.files[0].url.clone(),
-            sha512: project_version.files[0].hashes["sha512"]
-                .to_string()
-                .replace("\"", ""),
-            file_name: project_version.files[0].filename.clone(),
+        let project_version = match pin {
+            Some(version) => {
+                let project_version = client.get_project_version_by_number(mod_slug, version)?;
+                self.warn_incompatible(&project_version);
+                project_version
+            }
+            None => client.get_project_version(mod_slug, &self.version_info)?,
         };
-        self.mods.insert(
-            mod_slug.to_string(),
-            toml::Value::try_from(&mod_version).expect("try_from"),
-        );
+        let mut mod_version = PackMod::from_version(&project_version)?;
+        mod_version.pinned = pin.is_some();
+        mod_version.set_sides(&client.get_project(mod_slug)?);
+        self.mods.insert(mod_slug.to_string(), toml::Value::try_from(&mod_version)?);
         println!(
             "Found mod '{}' and added it to pack",
             mod_version.name.replace("\"", "")
         );
-        for dependency in project_version.dependencies {
-            let dep_slug = get_project_info(client, staging, dependency.project_id)
-                .expect("get_project_info")
-                .slug;
-            if dependency.dependency_type == "required" && !self.mods.contains_key(&dep_slug) {
-                println!("Dependency: ");
+        self.add_dependencies(std::slice::from_ref(&project_version), client)?;
+        Ok(project_version.game_versions)
+    }
+
+    /// pin a mod in the pack to the given version, it is kept on it when the pack is updated
+    pub fn pin_mod(&mut self, key: &str, version: &Version) -> Result<(), ApiError> {
+        let old = self.get_mod(key)?;
+        self.warn_incompatible(version);
+        let mut mod_version = PackMod::from_version(version)?;
+        mod_version.pinned = true;
+        mod_version.keep_extra_files(&old, version);
+        mod_version.keep_install_info(&old);
+        self.mods.insert(key.to_string(), toml::Value::try_from(&mod_version)?);
+        println!("Pinned {key} to {}", version.version_number);
+        Ok(())
+    }
 
-                self.add_mod(&dep_slug, client, staging);
+    /// unpin a mod, it is updated with the rest of the pack again
+    pub fn unpin_mod(&mut self, key: &str) -> Result<(), ApiError> {
+        let mut mod_version = self.get_mod(key)?;
+        mod_version.pinned = false;
+        self.mods.insert(key.to_string(), toml::Value::try_from(&mod_version)?);
+        println!("Unpinned {key}");
+        Ok(())
+    }
+
+    /// Choose which files of the mod's version are installed besides the mod itself.
+    ///
+    /// `file_names` replaces the current choice, an empty list installs only the mod.
+    pub fn set_extra_files(
+        &mut self,
+        key: &str,
+        version: &Version,
+        file_names: &[String],
+    ) -> Result<(), ApiError> {
+        let mut mod_version = self.get_mod(key)?;
+        if version.id != mod_version.version_id {
+            return Err(ApiError::invalid_data(format!(
+                "'{}' is not the version of {key} in the pack",
+                version.name
+            )));
+        }
+        let extra_files = version.extra_files();
+        mod_version.extra_files = Vec::new();
+        for file_name in file_names {
+            match extra_files.iter().find(|f| &f.filename == file_name) {
+                Some(file) => mod_version.extra_files.push((*file).into()),
+                None => {
+                    return Err(ApiError::invalid_data(format!(
+                        "'{}' has no extra file '{file_name}'",
+                        version.name
+                    )))
+                }
             }
         }
-        project_version.game_versions
+        self.mods.insert(key.to_string(), toml::Value::try_from(&mod_version)?);
+        Ok(())
     }
 
-    pub fn install(&self, client: &Client, config: &Configuration) {
-        for (key, value) in &self.mods {
-            let mod_version: PackMod = value.clone().try_into().expect("try_into");
-            let dl_path = config.install_path.clone().unwrap() + "/" + &mod_version.file_name;
-            println!("Downloading '{key}' to '{dl_path}' ");
-            let _ = client.download_file(&dl_path, &mod_version.file_url, &mod_version.sha512);
+    /// get the version of a mod that is in the pack
+    pub fn mod_version(&self, key: &str, client: &ModrinthClient) -> Result<Version, ApiError> {
+        let mod_version = self.get_mod(key)?;
+        if mod_version.version_id.is_empty() {
+            return Err(ApiError::invalid_data(format!("{key} has no version id")));
         }
+        client.get_version(&mod_version.version_id)
+    }
+
+    /// the entry of a mod in this pack
+    fn get_mod(&self, key: &str) -> Result<PackMod, ApiError> {
+        match self.mods.get(key) {
+            Some(value) => Ok(value.clone().try_into()?),
+            None => Err(ApiError::not_found().at(key)),
+        }
+    }
+
+    /// pinned versions are chosen by hand, so they may not fit the pack
+    fn warn_incompatible(&self, version: &Version) {
+        let mut version_desc = self.version_info.clone();
+        // a pinned version may be of any release type
+        version_desc.version_types = vec![version.version_type.clone()];
+        if !version_desc.mc_ver.is_latest() && !version_desc.check_version_compat(version)
+            || !version.supports_loader(&version_desc.loader)
+        {
+            println!(
+                "Warning: {} {} is not made for {} {}",
+                version.name, version.version_number, version_desc.mc_ver, version_desc.loader
+            );
+        }
+    }
+
+    /// Adds the required dependencies of the given versions.
+    ///
+    /// The dependency tree is walked one layer at a time, a layer takes three
+    /// requests: the exact versions some dependencies ask for, the projects
+    /// of the layer, and all versions of those projects, of which the newest
+    /// compatible one is chosen. Dependencies without a compatible version
+    /// are reported and skipped.
+    pub fn add_dependencies(
+        &mut self,
+        versions: &[Version],
+        client: &ModrinthClient,
+    ) -> Result<(), ApiError> {
+        let mut layer: Vec<Version> = versions.to_vec();
+        while !layer.is_empty() {
+            let mut project_ids: Vec<String> = Vec::new();
+            let mut version_ids: Vec<String> = Vec::new();
+            for dependency in layer.iter().flat_map(|v| &v.dependencies) {
+                if dependency.dependency_type != "required" {
+                    continue;
+                }
+                if let Some(project_id) = &dependency.project_id {
+                    if !project_ids.contains(project_id) {
+                        project_ids.push(project_id.clone());
+                    }
+                }
+                if let Some(version_id) = &dependency.version_id {
+                    if !version_ids.contains(version_id) {
+                        version_ids.push(version_id.clone());
+                    }
+                }
+            }
+            if project_ids.is_empty() && version_ids.is_empty() {
+                break;
+            }
+
+            // dependencies on an exact version are used if they fit the pack,
+            // dependencies that only name a version get their project from it
+            let pinned = client.get_versions(&version_ids)?;
+            for version in &pinned {
+                if !project_ids.contains(&version.project_id) {
+                    project_ids.push(version.project_id.clone());
+                }
+            }
+            let projects: Vec<Project> = client
+                .get_projects(&project_ids)?
+                .into_iter()
+                .filter(|project| !self.contains_project(project))
+                .collect();
+            let fits = |project: &Project, version: &Version| {
+                version.project_id == project.id && self.version_info.accepts(version)
+            };
+            let candidate_ids: Vec<String> = projects
+                .iter()
+                .filter(|project| !pinned.iter().any(|v| fits(project, v)))
+                .flat_map(|project| project.versions.iter().cloned())
+                .collect();
+            let candidates = client.get_versions(&candidate_ids)?;
+
+            let mut next_layer: Vec<Version> = Vec::new();
+            for project in projects {
+                say!("Dependency: ");
+                let newest = candidates
+                    .iter()
+                    .filter(|v| fits(&project, v))
+                    .max_by(|a, b| a.date_published.cmp(&b.date_published));
+                let dep_version = match pinned.iter().find(|v| fits(&project, v)).or(newest) {
+                    Some(v) => v.clone(),
+                    None => {
+                        eprintln!("{}", ApiError::no_compatible_version(&project.slug));
+                        continue;
+                    }
+                };
+                let mut mod_version = PackMod::from_version(&dep_version)?;
+                mod_version.set_sides(&project);
+                self.mods.insert(project.slug.clone(), toml::Value::try_from(&mod_version)?);
+                say!("Found mod '{}' and added it to pack", mod_version.name);
+                next_layer.push(dep_version);
+            }
+            layer = next_layer;
+        }
+        Ok(())
+    }
+
+    /// true if the project is part of the pack, either by its slug or its id
+    fn contains_project(&self, project: &Project) -> bool {
+        self.mods.contains_key(&project.slug)
+            || self.mods.contains_key(&project.id)
+            || self
+                .mods
+                .values()
+                .any(|value| value.get("project_id").and_then(|id| id.as_str()) == Some(&project.id))
+    }
+
+    /// Download every mod of the pack into the install path and copy the overrides.
+    ///
+    /// Files already in the store are taken from there, new downloads are
+    /// added to it. `config.workers` files are downloaded at the same time. Mods that fail
+    /// to download are collected and reported at the end, the others are
+    /// still installed.
+    pub fn install(&self, client: &ModrinthClient, config: &Configuration) -> Result<(), ApiError> {
+        let install_path = match &config.install_path {
+            Some(v) => v,
+            None => return Err(ApiError::invalid_data("No install path given")),
+        };
+        let downloads = self.downloads(install_path, config.install_target)?;
+        let skipped = self.skipped_mods(config.install_target)?;
+        if !skipped.is_empty() {
+            println!(
+                "Skipped {} mods not supported on the {}: {}",
+                skipped.len(),
+                config.install_target,
+                skipped.iter().map(|key| key.as_str()).collect::<Vec<&str>>().join(", ")
+            );
+        }
+        let mod_count = self.mods.len() - skipped.len();
+        println!(
+            "Installing {} files of {mod_count} mods to '{install_path}'",
+            downloads.len()
+        );
+
+        let total = downloads.iter().map(|(_, download)| download.size).sum();
+        let progress = Progress::new(total, downloads.len());
+        let store = JarStore::new(&config.store_path, config.link_mode);
+        let next = AtomicUsize::new(0);
+        let failures: Mutex<Vec<(&String, &FileDownload, ApiError)>> = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..config.workers.clamp(1, downloads.len().max(1)) {
+                scope.spawn(|| {
+                    while let Some((key, download)) =
+                        downloads.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        match install_file(client, &store, download, config, &progress) {
+                            Ok(true) => progress
+                                .println(&format!("Installed '{}' from the store", download.path)),
+                            Ok(false) => progress.println(&format!("Installed '{}'", download.path)),
+                            Err(e) => failures.lock().unwrap().push((key, download, e)),
+                        }
+                    }
+                });
+            }
+        });
+
+        if let Some(overrides) = &self.overrides {
+            install_overrides(overrides, install_path)?;
+        }
+        // side specific overrides go on top of the common ones
+        let side_overrides = match config.install_target {
+            InstallTarget::SERVER => &self.server_overrides,
+            InstallTarget::CLIENT | InstallTarget::BOTH => &self.client_overrides,
+        };
+        if let Some(overrides) = side_overrides {
+            install_overrides(overrides, install_path)?;
+        }
+        let failures = failures.into_inner().unwrap();
+        let failed: Vec<&FileDownload> = failures.iter().map(|(_, download, _)| *download).collect();
+        self.sync_install_path(install_path, &downloads, &failed, config)?;
+        if !failures.is_empty() {
+            eprintln!("The following downloads failed:");
+            for (key, download, e) in &failures {
+                eprintln!("  {key}: '{}': {e}", download.path);
+            }
+            let mut failed: Vec<&String> = failures.iter().map(|(key, _, _)| *key).collect();
+            failed.sort();
+            failed.dedup();
+            return Err(ApiError::invalid_data(format!(
+                "{} of {mod_count} mods could not be installed",
+                failed.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Remove or disable the files an earlier install put into the install path, that the pack no longer has,
+    /// and record what this install put there in the manifest.
+    ///
+    /// Files the user changed since they were installed are left alone, as
+    /// are jars mapito did not install, unless `remove_unknown` is set.
+    fn sync_install_path(
+        &self,
+        install_path: &str,
+        downloads: &[(&String, FileDownload)],
+        failed: &[&FileDownload],
+        config: &Configuration,
+    ) -> Result<(), ApiError> {
+        let old = InstallManifest::load(install_path)?;
+        let current: HashSet<String> = downloads
+            .iter()
+            .map(|(_, download)| manifest_name(install_path, &download.path))
+            .collect();
+        let mut manifest = InstallManifest {
+            pack: self.name.clone(),
+            files: BTreeMap::new(),
+        };
+
+        for name in old.stale_files(install_path, &current, config.remove_unknown)? {
+            let known = old.files.contains_key(&name);
+            if known && !old.is_unchanged(install_path, &name) {
+                println!("Kept '{name}', it changed since it was installed");
+                continue;
+            }
+            if config.stale_files == StaleAction::KEEP {
+                if let Some(sha512) = old.files.get(&name) {
+                    manifest.files.insert(name, sha512.clone());
+                }
+                continue;
+            }
+            match retire_file(install_path, &name, config.stale_files) {
+                Ok(()) if config.stale_files == StaleAction::REMOVE => println!("Removed stale '{name}'"),
+                Ok(()) => println!("Disabled stale '{name}'"),
+                Err(e) => eprintln!("Warning: could not {} '{name}': {e}", config.stale_files),
+            }
+        }
+
+        for (_, download) in downloads {
+            let name = manifest_name(install_path, &download.path);
+            if !failed.iter().any(|f| f.path == download.path) {
+                manifest.files.insert(name, download.sha512.clone());
+            } else if let Some(sha512) = old.files.get(&name) {
+                // the file of an earlier install is still there and still ours
+                manifest.files.insert(name, sha512.clone());
+            }
+        }
+        manifest.save(install_path)
     }
 }
 
+/// Install a single file, from the store if it has it, returns true if it did.
+///
+/// A failure to add a new download to the store is only a warning, the
+/// file is installed either way.
+fn install_file(
+    client: &ModrinthClient,
+    store: &JarStore,
+    download: &FileDownload,
+    config: &Configuration,
+    progress: &Progress,
+) -> Result<bool, ApiError> {
+    // files outside of the mods folder, like resource packs, may go to a folder that does not exist yet
+    if let Some(dir) = Path::new(&download.path).parent() {
+        create_dir_all(dir).map_err(|e| ApiError::from(e).at(dir.display()))?;
+    }
+    if store.place(&download.sha512, download.size, &download.path)? {
+        let file_progress = progress.start_file(&download.path, download.size);
+        file_progress.advance(download.size);
+        return Ok(true);
+    }
+    client
+        .http()
+        .download_file(download, &config.quarantine_path, progress)?;
+    if let Err(e) = store.insert(&download.sha512, &download.path) {
+        progress.println(&format!("Warning: could not add '{}' to the store: {e}", download.path));
+    }
+    Ok(false)
+}
+
+/// path of the pack file of the pack with the given name
+pub fn pack_file_path(name: &str, config: &Configuration) -> String {
+    config.pack_path.clone() + "/" + &name.to_lowercase().replace(" ", "-") + ".mtpck"
+}
+
 impl Display for Pack {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -196,7 +680,118 @@ impl Display for Pack {
                 .iter()
                 .map(|vt| vt.to_string() + " ")
                 .collect::<String>(),
-            self.version_info.loader.to_string()
+            self.version_info.loader
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::util::testing::serve_json;
+
+    fn version(
+        id: &str,
+        project_id: &str,
+        game_version: &str,
+        date: &str,
+        dependencies: Value,
+    ) -> Value {
+        json!({
+            "id": id, "project_id": project_id, "game_versions": [game_version],
+            "loaders": ["fabric"], "name": id, "version_number": id, "downloads": 0,
+            "version_type": "release", "date_published": date, "dependencies": dependencies,
+            "files": [{
+                "url": format!("https://cdn.modrinth.com/{id}.jar"), "filename": format!("{id}.jar"),
+                "hashes": {"sha512": "ab".repeat(64)}, "size": 1, "primary": true
+            }]
+        })
+    }
+
+    fn project(id: &str, slug: &str, versions: &[&str]) -> Value {
+        json!({
+            "id": id, "slug": slug, "project_type": "mod", "team": "t", "title": slug,
+            "description": "", "published": "", "updated": "",
+            "license": {"id": "MIT", "name": "MIT", "url": null}, "downloads": 0,
+            "game_versions": ["1.21.5"], "categories": [], "loaders": ["fabric"],
+            "source_url": null, "versions": versions
+        })
+    }
+
+    /// Fabric API through its project, Cloth Config only through a version
+    fn respond(path: &str) -> Value {
+        match path {
+            _ if path.starts_with("/versions?") && path.contains("CC1") => json!([version(
+                "CC1",
+                "9s6osm5g",
+                "1.21.5",
+                "2025-03-01T00:00:00Z",
+                json!([])
+            )]),
+            _ if path.starts_with("/projects?") => json!([
+                project("P7dR8mSH", "fabric-api", &["FA1", "FA2", "FA3"]),
+                project("9s6osm5g", "cloth-config", &["CC1"])
+            ]),
+            _ if path.starts_with("/versions?") => json!([
+                version(
+                    "FA3",
+                    "P7dR8mSH",
+                    "1.21.4",
+                    "2025-04-01T00:00:00Z",
+                    json!([])
+                ),
+                version(
+                    "FA1",
+                    "P7dR8mSH",
+                    "1.21.5",
+                    "2025-02-01T00:00:00Z",
+                    json!([])
+                ),
+                version(
+                    "FA2",
+                    "P7dR8mSH",
+                    "1.21.5",
+                    "2025-03-01T00:00:00Z",
+                    json!([])
+                )
+            ]),
+            _ => json!(null),
+        }
+    }
+
+    #[test]
+    fn dependencies_of_a_layer_take_three_requests() {
+        let mut pack = Pack::new();
+        pack.version_info.mc_ver = MCVersion::from_str("1.21.5").unwrap();
+        let dependencies = json!([
+            {"project_id": "P7dR8mSH", "version_id": null, "dependency_type": "required"},
+            {"project_id": null, "version_id": "CC1", "dependency_type": "required"},
+            {"project_id": "mOgUt4GM", "version_id": null, "dependency_type": "optional"}
+        ]);
+        let sodium: Version = serde_json::from_value(version(
+            "SO1",
+            "AANobbMI",
+            "1.21.5",
+            "2025-03-01T00:00:00Z",
+            dependencies,
+        ))
+        .unwrap();
+        // a fourth request would be refused
+        let client = ModrinthClient::new(&serve_json(3, respond));
+
+        pack.add_dependencies(&[sodium], &client).unwrap();
+
+        let version_of = |key: &str| {
+            pack.mods[key]["version_number"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        // the newest version for the Minecraft version of the pack
+        assert_eq!(version_of("fabric-api"), "FA2");
+        assert_eq!(version_of("cloth-config"), "CC1");
+        assert_eq!(pack.mods.len(), 2);
+    }
+}
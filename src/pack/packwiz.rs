@@ -0,0 +1,568 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read, write},
+    path::Path,
+    str::FromStr,
+};
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{
+    config::Configuration,
+    mc_info::{MCVersion, LOADER, VT},
+    mrapi::{
+        client::ModrinthClient,
+        defines::{SideSupport, Version},
+    },
+    pack::{
+        file_hash,
+        mrpack::{latest_loader_version, overrides_path, OVERRIDES},
+        pack::Pack,
+        split_instance_path, PackMod, MODS_FOLDER,
+    },
+    util::error::ApiError,
+};
+
+pub const PACK_FILE: &str = "pack.toml";
+pub const INDEX_FILE: &str = "index.toml";
+pub const META_SUFFIX: &str = ".pw.toml";
+pub const PACK_FORMAT: &str = "packwiz:1.1.0";
+pub const HASH_FORMAT: &str = "sha256";
+
+/// pack.toml, the entry point of every packwiz pack
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwPack {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    pub pack_format: String,
+    pub index: PwIndexRef,
+    pub versions: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwIndexRef {
+    pub file: String,
+    pub hash_format: String,
+    pub hash: String,
+}
+
+/// index.toml, lists every file of the pack
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwIndex {
+    pub hash_format: String,
+    #[serde(default)]
+    pub files: Vec<PwIndexFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwIndexFile {
+    pub file: String,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_format: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub metafile: bool,
+}
+
+/// a mods/*.pw.toml metafile, describing where to download a single mod
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwMod {
+    pub name: String,
+    pub filename: String,
+    #[serde(default = "default_side")]
+    pub side: String,
+    pub download: PwDownload,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<PwUpdate>,
+    /// pinned mods are not updated
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pin: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwDownload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub hash_format: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PwUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modrinth: Option<PwModrinth>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PwModrinth {
+    pub mod_id: String,
+    pub version: String,
+}
+
+/// write the metafile of a mod and add it to the index
+fn write_metafile(
+    root: &Path,
+    file: String,
+    pw_mod: &PwMod,
+    index: &mut PwIndex,
+) -> Result<(), ApiError> {
+    let body = toml::to_string(pw_mod)?;
+    if let Some(parent) = root.join(&file).parent() {
+        create_dir_all(parent)?;
+    }
+    write(root.join(&file), &body)?;
+    index.files.push(PwIndexFile {
+        file,
+        hash: sha256(body.as_bytes()),
+        hash_format: None,
+        metafile: true,
+    });
+    Ok(())
+}
+
+fn default_side() -> String {
+    "both".to_string()
+}
+
+/// the packwiz side of a mod, packwiz only knows mods that are for one side or both
+fn pw_side(pack_mod: &PackMod) -> String {
+    match (pack_mod.client_side, pack_mod.server_side) {
+        (_, SideSupport::UNSUPPORTED) => "client".to_string(),
+        (SideSupport::UNSUPPORTED, _) => "server".to_string(),
+        _ => default_side(),
+    }
+}
+
+/// Read a packwiz pack from the folder containing its pack.toml.
+///
+/// The index and every file it lists are checked against their hashes
+/// before they are used. Every file of the index that is not a metafile is
+/// copied to the overrides of the new pack. The versions of the metafiles
+/// are looked up with one request, metafiles without update info become
+/// external mods. Files whose sha512 hash is still unknown are downloaded
+/// once to compute it, mapito checks it on install.
+pub fn import_packwiz(
+    dir: &str,
+    client: &ModrinthClient,
+    config: &Configuration,
+) -> Result<Pack, ApiError> {
+    let root = Path::new(dir);
+    let pw_pack: PwPack = read_toml(&root.join(PACK_FILE), None)?;
+    let index_path = root.join(&pw_pack.index.file);
+    let index: PwIndex = read_toml(
+        &index_path,
+        Some((&pw_pack.index.hash_format, &pw_pack.index.hash)),
+    )?;
+    let index_root = match index_path.parent() {
+        Some(p) => p.to_path_buf(),
+        None => root.to_path_buf(),
+    };
+
+    let mut pack = Pack::new();
+    pack.name = pw_pack.name.clone();
+    if !pw_pack.version.is_empty() {
+        pack.version = Some(pw_pack.version.clone());
+    }
+    for (component, version) in &pw_pack.versions {
+        match component.as_str() {
+            "minecraft" => {
+                pack.version_info.mc_ver =
+                    MCVersion::from_str(version).map_err(ApiError::invalid_data)?
+            }
+            other => match LOADER::from_str(other) {
+                Ok(loader) => {
+                    pack.version_info.loader = loader;
+                    pack.loader_version = Some(version.clone());
+                }
+                Err(_) => println!("Warning: unknown version entry '{component}' in {PACK_FILE}."),
+            },
+        }
+    }
+    if !pw_pack.versions.contains_key("minecraft") {
+        return Err(ApiError::invalid_data(
+            "Pack does not specify a Minecraft version",
+        ));
+    }
+
+    let overrides_dir = overrides_path(&pack, config, OVERRIDES);
+    let mut has_overrides = false;
+    let mut entries = Vec::new();
+    for file in &index.files {
+        let (folder, name) = split_instance_path(&file.file)?;
+        let path = index_root.join(&file.file);
+        let hash_format = file.hash_format.as_deref().unwrap_or(&index.hash_format);
+        if !file.metafile {
+            let body = read_verified(&path, hash_format, &file.hash)?;
+            let target = Path::new(&overrides_dir).join(&file.file);
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent)?;
+            }
+            write(&target, body)?;
+            has_overrides = true;
+            continue;
+        }
+
+        let pw_mod: PwMod = read_toml(&path, Some((hash_format, &file.hash)))?;
+        let key = name.strip_suffix(META_SUFFIX).unwrap_or(&name).to_string();
+        entries.push((key, pw_mod_to_pack_mod(pw_mod, folder)?));
+    }
+
+    let version_ids: Vec<String> = entries
+        .iter()
+        .filter(|(_, pack_mod)| !pack_mod.external)
+        .map(|(_, pack_mod)| pack_mod.version_id.clone())
+        .collect();
+    let versions = client.get_versions(&version_ids)?;
+    for (key, mut pack_mod) in entries {
+        if !pack_mod.external {
+            match versions.iter().find(|v| v.id == pack_mod.version_id) {
+                Some(version) => apply_version(&mut pack_mod, version),
+                None => {
+                    println!("Warning: version of '{key}' not found on modrinth, it will not be updated.");
+                    pack_mod.external = true;
+                }
+            }
+        }
+        if pack_mod.sha512.is_empty() {
+            println!("Computing hashes for '{key}'");
+            pack_mod.fetch_file_info(client.http())?;
+        }
+        pack.mods.insert(key, toml::Value::try_from(&pack_mod)?);
+    }
+    if has_overrides {
+        pack.overrides = Some(overrides_dir);
+    }
+
+    println!(
+        "Imported {} mods from {}, Minecraft-{}",
+        pack.mods.len(),
+        pack.name,
+        pack.version_info.mc_ver
+    );
+    Ok(pack)
+}
+
+/// Write the given pack as a packwiz pack into `dir`.
+///
+/// The overrides of the pack are copied into `dir` and listed in the index
+/// next to the metafiles of the mods.
+pub fn export_packwiz(pack: &Pack, dir: &str, client: &Client) -> Result<(), ApiError> {
+    if pack.version_info.mc_ver.is_latest() {
+        return Err(ApiError::invalid_data(
+            "Cannot export a pack without a fixed Minecraft version",
+        ));
+    }
+    let loader_version = match &pack.loader_version {
+        Some(v) => v.clone(),
+        None => latest_loader_version(client, pack)?,
+    };
+    let root = Path::new(dir);
+    create_dir_all(root.join(MODS_FOLDER))?;
+
+    let mut index = PwIndex {
+        hash_format: HASH_FORMAT.to_string(),
+        files: Vec::new(),
+    };
+    for (key, value) in &pack.mods {
+        let pack_mod: PackMod = value.clone().try_into()?;
+        let folder = pack_mod.folder.as_deref();
+        // packwiz has one file per metafile, extra files become mods without update info
+        for (i, extra) in pack_mod.extra_files.iter().enumerate() {
+            let pw_mod = PwMod {
+                name: extra.file_name.clone(),
+                filename: extra.file_name.clone(),
+                side: pw_side(&pack_mod),
+                download: PwDownload {
+                    url: Some(extra.file_url.clone()),
+                    hash_format: "sha512".to_string(),
+                    hash: extra.sha512.clone(),
+                },
+                update: None,
+                pin: pack_mod.pinned,
+            };
+            let extra_folder = extra.folder().or(folder).unwrap_or(MODS_FOLDER);
+            let file = format!("{extra_folder}/{key}-extra-{i}{META_SUFFIX}");
+            write_metafile(root, file, &pw_mod, &mut index)?;
+        }
+        let side = pw_side(&pack_mod);
+        let file = folder.unwrap_or(MODS_FOLDER).to_string() + "/" + key + META_SUFFIX;
+        let pw_mod = PwMod {
+            name: pack_mod.name,
+            filename: pack_mod.file_name,
+            side,
+            download: PwDownload {
+                url: Some(pack_mod.file_url),
+                hash_format: "sha512".to_string(),
+                hash: pack_mod.sha512,
+            },
+            update: match pack_mod.project_id.is_empty() {
+                true => None,
+                false => Some(PwUpdate {
+                    modrinth: Some(PwModrinth {
+                        mod_id: pack_mod.project_id,
+                        version: pack_mod.version_id,
+                    }),
+                }),
+            },
+            pin: pack_mod.pinned,
+        };
+        write_metafile(root, file, &pw_mod, &mut index)?;
+    }
+
+    if let Some(overrides) = &pack.overrides {
+        copy_overrides(Path::new(overrides), root, "", &mut index)?;
+    }
+
+    let index_body = toml::to_string(&index)?;
+    write(root.join(INDEX_FILE), &index_body)?;
+
+    let mut versions = BTreeMap::new();
+    versions.insert(
+        "minecraft".to_string(),
+        pack.version_info.mc_ver.to_string(),
+    );
+    versions.insert(pack.version_info.loader.to_string(), loader_version);
+    let pw_pack = PwPack {
+        name: pack.name.clone(),
+        author: String::new(),
+        version: pack.version.clone().unwrap_or_default(),
+        pack_format: PACK_FORMAT.to_string(),
+        index: PwIndexRef {
+            file: INDEX_FILE.to_string(),
+            hash_format: HASH_FORMAT.to_string(),
+            hash: sha256(index_body.as_bytes()),
+        },
+        versions,
+    };
+    write(root.join(PACK_FILE), toml::to_string(&pw_pack)?)?;
+
+    println!("Exported {} to '{dir}'", pack.name);
+    Ok(())
+}
+
+/// Map a packwiz metafile in `folder` to a PackMod.
+///
+/// The version number is filled in by [`apply_version`], metafiles without
+/// update info are external. The sha512 hash stays empty if the metafile
+/// uses another format.
+fn pw_mod_to_pack_mod(pw_mod: PwMod, folder: Option<String>) -> Result<PackMod, ApiError> {
+    let file_url = match pw_mod.download.url {
+        Some(url) => url,
+        None => {
+            return Err(ApiError::invalid_data(format!(
+                "'{}' has no download url",
+                pw_mod.name
+            )))
+        }
+    };
+    let (sha512, sha1) = match pw_mod.download.hash_format.as_str() {
+        "sha512" => (pw_mod.download.hash, String::new()),
+        "sha1" => (String::new(), pw_mod.download.hash),
+        other => {
+            return Err(ApiError::invalid_data(format!(
+                "Unsupported hash format '{other}' for '{}'",
+                pw_mod.name
+            )))
+        }
+    };
+    let pinned = pw_mod.pin;
+    let (client_side, server_side) = match pw_mod.side.as_str() {
+        "client" => (SideSupport::REQUIRED, SideSupport::UNSUPPORTED),
+        "server" => (SideSupport::UNSUPPORTED, SideSupport::REQUIRED),
+        _ => (SideSupport::UNKNOWN, SideSupport::UNKNOWN),
+    };
+    let (project_id, version_id) = match pw_mod.update.and_then(|u| u.modrinth) {
+        Some(modrinth) => (modrinth.mod_id, modrinth.version),
+        None => (String::new(), String::new()),
+    };
+    let external = project_id.is_empty() || version_id.is_empty();
+    Ok(PackMod {
+        name: pw_mod.name,
+        verstion_type: VT::RELEASE,
+        version_number: String::new(),
+        file_url,
+        file_name: pw_mod.filename,
+        sha512,
+        sha1,
+        size: 0,
+        project_id,
+        version_id,
+        pinned,
+        extra_files: Vec::new(),
+        client_side,
+        server_side,
+        folder,
+        external,
+    })
+}
+
+/// Fill in what the metafile does not know from the modrinth version of the mod.
+///
+/// The hashes and size are only taken from the file of the version that has
+/// the hash of the metafile.
+fn apply_version(pack_mod: &mut PackMod, version: &Version) {
+    pack_mod.name = version.name.clone();
+    pack_mod.verstion_type = version.version_type.clone();
+    pack_mod.version_number = version.version_number.clone();
+    let file = version.files.iter().find(|f| {
+        (!pack_mod.sha512.is_empty() && file_hash(f, "sha512") == pack_mod.sha512)
+            || (!pack_mod.sha1.is_empty() && file_hash(f, "sha1") == pack_mod.sha1)
+    });
+    if let Some(file) = file {
+        pack_mod.sha512 = file_hash(file, "sha512");
+        pack_mod.sha1 = file_hash(file, "sha1");
+        pack_mod.size = file.size;
+    }
+}
+
+/// copy the overrides into the pack folder and add them to the index
+fn copy_overrides(src: &Path, root: &Path, rel: &str, index: &mut PwIndex) -> Result<(), ApiError> {
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let rel_path = match rel.is_empty() {
+            true => entry.file_name().to_string_lossy().to_string(),
+            false => rel.to_string() + "/" + &entry.file_name().to_string_lossy(),
+        };
+        if entry.file_type()?.is_dir() {
+            create_dir_all(root.join(&rel_path))?;
+            copy_overrides(&entry.path(), root, &rel_path, index)?;
+        } else {
+            let body = std::fs::read(entry.path())?;
+            write(root.join(&rel_path), &body)?;
+            index.files.push(PwIndexFile {
+                file: rel_path,
+                hash: sha256(&body),
+                hash_format: None,
+                metafile: false,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// read a toml file, checking it against the `(format, hash)` the index lists for it first
+fn read_toml<T: serde::de::DeserializeOwned>(
+    path: &Path,
+    hash: Option<(&str, &str)>,
+) -> Result<T, ApiError> {
+    let body = match hash {
+        Some((format, hash)) => read_verified(path, format, hash)?,
+        None => read(path).map_err(|e| ApiError::from(e).at(path.display()))?,
+    };
+    let body = String::from_utf8(body)
+        .map_err(|e| ApiError::invalid_data(e.to_string()).at(path.display()))?;
+    toml::from_str(&body).map_err(|e| ApiError::from(e).at(path.display()))
+}
+
+/// read a file of the pack, it has to match the hash pack.toml or the index lists for it
+fn read_verified(path: &Path, format: &str, hash: &str) -> Result<Vec<u8>, ApiError> {
+    let body = read(path).map_err(|e| ApiError::from(e).at(path.display()))?;
+    let actual = match format {
+        "sha256" => sha256(&body),
+        "sha512" => base16ct::lower::encode_string(&Sha512::digest(&body)),
+        "sha1" => base16ct::lower::encode_string(&Sha1::digest(&body)),
+        other => {
+            return Err(
+                ApiError::invalid_data(format!("Unsupported hash format '{other}'"))
+                    .at(path.display()),
+            )
+        }
+    };
+    if !actual.eq_ignore_ascii_case(hash) {
+        return Err(
+            ApiError::invalid_data("File does not match the hash the pack lists for it")
+                .at(path.display()),
+        );
+    }
+    Ok(body)
+}
+
+fn sha256(body: &[u8]) -> String {
+    base16ct::lower::encode_string(&Sha256::digest(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pack::tests::{respond, round_trip_pack, SODIUM_VERSION_ID};
+    use crate::util::testing::{serve_json, TempDir};
+
+    #[test]
+    fn packwiz_round_trip() {
+        let dir = TempDir::new("packwiz-test");
+        let pack = round_trip_pack(&dir);
+        let pw_dir = dir.join("packwiz").to_string_lossy().to_string();
+        export_packwiz(&pack, &pw_dir, &Client::new()).unwrap();
+        assert!(Path::new(&pw_dir)
+            .join("resourcepacks/faithful.pw.toml")
+            .is_file());
+
+        let client = ModrinthClient::new(&serve_json(1, respond));
+        let config = Configuration {
+            pack_path: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let imported = import_packwiz(&pw_dir, &client, &config).unwrap();
+
+        assert_eq!(imported.name, pack.name);
+        assert_eq!(imported.version, pack.version);
+        assert_eq!(imported.version_info.mc_ver, pack.version_info.mc_ver);
+        assert_eq!(imported.version_info.loader, pack.version_info.loader);
+        assert_eq!(imported.loader_version, pack.loader_version);
+        // the metafile of sodium only has the ids and the hash, the rest comes from its version
+        assert_eq!(imported.mods["sodium"], pack.mods["sodium"]);
+        assert_eq!(
+            imported.mods["fresh-animations"],
+            pack.mods["fresh-animations"]
+        );
+        let sodium: PackMod = imported.mods["sodium"].clone().try_into().unwrap();
+        assert_eq!(sodium.version_id, SODIUM_VERSION_ID);
+        // packwiz does not record the size and sha1 of files without update info
+        let faithful: PackMod = imported.mods["faithful"].clone().try_into().unwrap();
+        let expected: PackMod = pack.mods["faithful"].clone().try_into().unwrap();
+        assert!(faithful.external);
+        assert_eq!(faithful.folder, expected.folder);
+        assert_eq!(faithful.file_url, expected.file_url);
+        assert_eq!(faithful.sha512, expected.sha512);
+
+        let overrides = imported.overrides.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(Path::new(&overrides).join("config/sodium-options.json"))
+                .unwrap(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn import_rejects_changed_files() {
+        let dir = TempDir::new("packwiz-test-tampered");
+        let pack = round_trip_pack(&dir);
+        let pw_dir = dir.join("packwiz");
+        export_packwiz(&pack, &pw_dir.to_string_lossy(), &Client::new()).unwrap();
+        let metafile = pw_dir.join("mods/sodium.pw.toml");
+        let body = std::fs::read_to_string(&metafile).unwrap();
+        std::fs::write(&metafile, body.replace("cdn.modrinth.com", "example.com")).unwrap();
+
+        // the hash check fails before anything is requested
+        let client = ModrinthClient::new("http://127.0.0.1:9");
+        let config = Configuration {
+            pack_path: dir.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let err = import_packwiz(&pw_dir.to_string_lossy(), &client, &config).unwrap_err();
+        assert!(err.to_string().contains("does not match the hash"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::read,
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha512};
+
+use crate::{
+    client::FileDownload,
+    config::Configuration,
+    pack::{
+        file_name,
+        manifest::{manifest_name, InstallManifest, StaleAction},
+        pack::Pack,
+        store::JarStore,
+        PackMod,
+    },
+    util::error::ApiError,
+};
+
+/// What an install or update would change, computed without writing anything.
+#[derive(Serialize, Debug, Default)]
+pub struct Plan {
+    pub pack: String,
+    /// the install path the files are compared against, None if they are compared against the pack before an update
+    pub install_path: Option<String>,
+    pub bumps: Vec<VersionBump>,
+    /// mods that do not support the install target
+    pub skipped: Vec<String>,
+    /// files that are not there yet
+    pub downloads: Vec<PlannedFile>,
+    /// files that are there with other contents
+    pub replacements: Vec<PlannedFile>,
+    pub removals: Vec<PlannedRemoval>,
+    /// bytes of the downloads and replacements that are not in the store
+    pub download_size: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VersionBump {
+    pub slug: String,
+    /// None for mods new to the pack, e.g. new dependencies
+    pub old_version: Option<String>,
+    pub new_version: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlannedFile {
+    pub slug: String,
+    pub file_name: String,
+    pub size: u64,
+    pub from_store: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PlannedRemoval {
+    pub file_name: String,
+    pub action: StaleAction,
+}
+
+impl Plan {
+    /// What `--pack install` would do to the install path.
+    pub fn install(pack: &Pack, config: &Configuration) -> Result<Self, ApiError> {
+        let install_path = match &config.install_path {
+            Some(v) => v,
+            None => return Err(ApiError::invalid_data("No install path given")),
+        };
+        let mut plan = Plan {
+            pack: pack.name.clone(),
+            install_path: Some(install_path.clone()),
+            skipped: pack
+                .skipped_mods(config.install_target)?
+                .into_iter()
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+        let downloads = pack.downloads(install_path, config.install_target)?;
+        let mut current = BTreeMap::new();
+        for (_, download) in &downloads {
+            if let Ok(body) = read(&download.path) {
+                let sha512 = base16ct::lower::encode_string(&Sha512::digest(&body));
+                current.insert(manifest_name(install_path, &download.path), sha512);
+            }
+        }
+        plan.add_files(&downloads, install_path, &current, config);
+
+        if config.stale_files != StaleAction::KEEP {
+            let manifest = InstallManifest::load(install_path)?;
+            let names: HashSet<String> = downloads
+                .iter()
+                .map(|(_, download)| manifest_name(install_path, &download.path))
+                .collect();
+            for name in manifest.stale_files(install_path, &names, config.remove_unknown)? {
+                // files the user changed are kept on install
+                if manifest.files.contains_key(&name) && !manifest.is_unchanged(install_path, &name)
+                {
+                    continue;
+                }
+                plan.removals.push(PlannedRemoval {
+                    file_name: name,
+                    action: config.stale_files,
+                });
+            }
+        }
+        Ok(plan)
+    }
+
+    /// What `--pack update` would change from `old` to `new`.
+    ///
+    /// The files are compared against the install path if one is
+    /// configured, against the files of `old` otherwise.
+    pub fn update(old: &Pack, new: &Pack, config: &Configuration) -> Result<Self, ApiError> {
+        let mut plan = match &config.install_path {
+            Some(_) => Plan::install(new, config)?,
+            None => {
+                let mut plan = Plan {
+                    pack: new.name.clone(),
+                    ..Default::default()
+                };
+                let old_downloads = old.downloads("", config.install_target)?;
+                let new_downloads = new.downloads("", config.install_target)?;
+                let current = old_downloads
+                    .iter()
+                    .map(|(_, download)| {
+                        (manifest_name("", &download.path), download.sha512.clone())
+                    })
+                    .collect();
+                plan.add_files(&new_downloads, "", &current, config);
+                let names: HashSet<String> = new_downloads
+                    .iter()
+                    .map(|(_, download)| manifest_name("", &download.path))
+                    .collect();
+                for (_, download) in &old_downloads {
+                    let name = manifest_name("", &download.path);
+                    if !names.contains(&name) {
+                        plan.removals.push(PlannedRemoval {
+                            file_name: file_name(&name).to_string(),
+                            action: config.stale_files,
+                        });
+                    }
+                }
+                plan
+            }
+        };
+        plan.skipped = new
+            .skipped_mods(config.install_target)?
+            .into_iter()
+            .cloned()
+            .collect();
+        for (key, value) in &new.mods {
+            let new_version: PackMod = value.clone().try_into()?;
+            let old_version = match old.mods.get(key) {
+                Some(value) => Some(value.clone().try_into::<PackMod>()?.version_number),
+                None => None,
+            };
+            if old_version.as_ref() != Some(&new_version.version_number) {
+                plan.bumps.push(VersionBump {
+                    slug: key.clone(),
+                    old_version,
+                    new_version: new_version.version_number,
+                });
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Sort the downloads into new files and replacements.
+    ///
+    /// `current` maps the [`manifest_name`] in `dir` of the files that are
+    /// already there to their hash, files with the same hash are skipped.
+    fn add_files(
+        &mut self,
+        downloads: &[(&String, FileDownload)],
+        dir: &str,
+        current: &BTreeMap<String, String>,
+        config: &Configuration,
+    ) {
+        let store = JarStore::new(&config.store_path, config.link_mode);
+        for (key, download) in downloads {
+            let planned = PlannedFile {
+                slug: key.to_string(),
+                file_name: file_name(&download.path).to_string(),
+                size: download.size,
+                from_store: store.contains(&download.sha512),
+            };
+            let replaces = match current.get(&manifest_name(dir, &download.path)) {
+                Some(sha512) if *sha512 == download.sha512 => continue,
+                Some(_) => true,
+                None => false,
+            };
+            if !planned.from_store {
+                self.download_size += download.size;
+            }
+            match replaces {
+                true => self.replacements.push(planned),
+                false => self.downloads.push(planned),
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bumps.is_empty()
+            && self.downloads.is_empty()
+            && self.replacements.is_empty()
+            && self.removals.is_empty()
+    }
+
+    pub fn print(&self) {
+        match &self.install_path {
+            Some(path) => println!("Plan for {} in '{path}':", self.pack),
+            None => println!("Plan for {}:", self.pack),
+        }
+        if !self.skipped.is_empty() {
+            println!(
+                "Skipped, not supported on the target side: {}",
+                self.skipped.join(", ")
+            );
+        }
+        if self.is_empty() {
+            println!("  Nothing to do");
+            return;
+        }
+        if !self.bumps.is_empty() {
+            println!("Version bumps:");
+            for bump in &self.bumps {
+                println!(
+                    "  {}: {} -> {}",
+                    bump.slug,
+                    bump.old_version.as_deref().unwrap_or("new"),
+                    bump.new_version
+                );
+            }
+        }
+        print_files("Downloads", &self.downloads);
+        print_files("Replacements", &self.replacements);
+        if !self.removals.is_empty() {
+            println!("Removals:");
+            for removal in &self.removals {
+                println!("  {} ({})", removal.file_name, removal.action);
+            }
+        }
+        println!("Total download size: {:.2} MiB", mib(self.download_size));
+    }
+
+    pub fn to_json(&self) -> Result<String, ApiError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn print_files(title: &str, files: &[PlannedFile]) {
+    if files.is_empty() {
+        return;
+    }
+    println!("{title}:");
+    for file in files {
+        println!(
+            "  {}: {} ({:.2} MiB{})",
+            file.slug,
+            file.file_name,
+            mib(file.size),
+            if file.from_store {
+                ", from the store"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+fn mib(bytes: u64) -> f64 {
+    bytes as f64 / 1048576_f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::TempDir;
+
+    fn download(name: &str, sha512: &str, size: u64) -> FileDownload {
+        FileDownload {
+            path: format!("/mc/mods/{name}"),
+            url: format!("https://cdn.modrinth.com/{name}"),
+            sha512: sha512.to_string(),
+            size,
+        }
+    }
+
+    /// a config whose store holds a file with the given hash, the store is removed with the returned dir
+    fn config(name: &str, stored: &str) -> (Configuration, TempDir) {
+        let store_path = TempDir::new(&format!("plan-test-{name}"));
+        std::fs::create_dir_all(store_path.join(&stored[..2])).unwrap();
+        std::fs::write(store_path.join(&stored[..2]).join(stored), b"").unwrap();
+        let config = Configuration {
+            store_path: store_path.path_str(),
+            ..Default::default()
+        };
+        (config, store_path)
+    }
+
+    #[test]
+    fn add_files_sorts_by_what_is_there() {
+        let (config, _store) = config("sort", "ff00");
+        let (sodium, lithium, iris) = (
+            "sodium".to_string(),
+            "lithium".to_string(),
+            "iris".to_string(),
+        );
+        let downloads = [
+            (&sodium, download("sodium.jar", "aa00", 100)),
+            (&lithium, download("lithium.jar", "bb00", 200)),
+            (&iris, download("iris.jar", "cc00", 400)),
+        ];
+        let current = BTreeMap::from([
+            ("sodium.jar".to_string(), "aa00".to_string()),
+            ("lithium.jar".to_string(), "bb01".to_string()),
+        ]);
+        let mut plan = Plan::default();
+        plan.add_files(&downloads, "/mc/mods", &current, &config);
+
+        let names =
+            |files: &[PlannedFile]| files.iter().map(|f| f.slug.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&plan.replacements), ["lithium"]);
+        assert_eq!(names(&plan.downloads), ["iris"]);
+        assert_eq!(plan.download_size, 600);
+        assert!(!plan.is_empty());
+    }
+
+    #[test]
+    fn add_files_does_not_count_store_files() {
+        let (config, _store) = config("store", "ff00");
+        let (sodium, lithium) = ("sodium".to_string(), "lithium".to_string());
+        let downloads = [
+            (&sodium, download("sodium.jar", "ff00", 100)),
+            (&lithium, download("lithium.jar", "bb00", 200)),
+        ];
+        let current = BTreeMap::from([("sodium.jar".to_string(), "aa00".to_string())]);
+        let mut plan = Plan::default();
+        plan.add_files(&downloads, "/mc/mods", &current, &config);
+
+        assert!(plan.replacements[0].from_store);
+        assert!(!plan.downloads[0].from_store);
+        assert_eq!(plan.download_size, 200);
+    }
+
+    #[test]
+    fn add_files_with_everything_in_place() {
+        let (config, _store) = config("same", "ff00");
+        let sodium = "sodium".to_string();
+        let downloads = [(&sodium, download("sodium.jar", "aa00", 100))];
+        let current = BTreeMap::from([("sodium.jar".to_string(), "aa00".to_string())]);
+        let mut plan = Plan::default();
+        plan.add_files(&downloads, "/mc/mods", &current, &config);
+
+        assert!(plan.is_empty());
+        assert_eq!(plan.download_size, 0);
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::{copy, create_dir_all, hard_link, read_dir, remove_dir, remove_file, rename, File},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::{client::PART_SUFFIX, config::Configuration, pack::pack::Pack, util::error::ApiError};
+
+#[derive(Debug, Clone)]
+pub enum CacheAction {
+    GC,
+}
+
+impl Display for CacheAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_display = match self {
+            CacheAction::GC => "gc",
+        };
+        write!(f, "{}", to_display)
+    }
+}
+
+impl FromStr for CacheAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gc" => Ok(Self::GC),
+            _ => Err("Invalid input".to_string()),
+        }
+    }
+}
+
+/// How a file from the store is put into an install path.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    /// the installed file is the store file, takes no space, store and install path have to be on the same file system
+    HARDLINK,
+    /// a copy on write clone where the file system supports it, a normal copy otherwise
+    REFLINK,
+    COPY,
+}
+
+impl Display for LinkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to_write = match self {
+            Self::HARDLINK => "hardlink",
+            Self::REFLINK => "reflink",
+            Self::COPY => "copy",
+        };
+        write!(f, "{}", to_write)
+    }
+}
+
+impl FromStr for LinkMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hardlink" => Ok(Self::HARDLINK),
+            "reflink" => Ok(Self::REFLINK),
+            "copy" => Ok(Self::COPY),
+            _ => Err("invalid link mode, use one of: hardlink, reflink, copy".to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LinkMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match Self::from_str(&String::deserialize(deserializer)?) {
+            Ok(mode) => Ok(mode),
+            Err(e) => Err(serde::de::Error::custom(e)),
+        }
+    }
+}
+
+/// A store of downloaded files shared by all packs, keyed by their sha512 hash.
+///
+/// Files are only added after they passed their hash check, so the name of
+/// a file in the store is its hash. They are checked against it again
+/// before they are installed. Store files are read only, except in
+/// [`LinkMode::HARDLINK`], where the installed file is the store file.
+#[derive(Debug, Clone)]
+pub struct JarStore {
+    dir: PathBuf,
+    mode: LinkMode,
+}
+
+impl JarStore {
+    pub fn new(dir: &str, mode: LinkMode) -> Self {
+        JarStore {
+            dir: PathBuf::from(dir),
+            mode,
+        }
+    }
+
+    /// where the file with the given hash is stored, the first two characters fan out into folders
+    fn path(&self, sha512: &str) -> Option<PathBuf> {
+        // the hash becomes a path, so it must not contain anything but hex digits
+        if sha512.len() < 2 || !sha512.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(self.dir.join(&sha512[..2]).join(sha512))
+    }
+
+    pub fn contains(&self, sha512: &str) -> bool {
+        self.path(sha512).is_some_and(|path| path.is_file())
+    }
+
+    /// Put the stored file with the given hash and size at `target`, a size of 0 is not checked.
+    ///
+    /// Returns false if the store does not have it. A stored file that does
+    /// not match its hash or size is removed from the store and counts as
+    /// missing. Hardlinks fall back to a copy if the store is on another file
+    /// system.
+    pub fn place(&self, sha512: &str, size: u64, target: &str) -> Result<bool, ApiError> {
+        let source = match self.path(sha512) {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(false),
+        };
+        if !is_intact(&source, sha512, size) {
+            let _ = remove_file(&source);
+            return Ok(false);
+        }
+        let part = target.to_string() + PART_SUFFIX;
+        let _ = remove_file(&part);
+        link(&source, Path::new(&part), self.mode).map_err(|e| e.at(target))?;
+        // copies keep the read only mode of the store file, installed files are writable like fresh downloads
+        if self.mode != LinkMode::HARDLINK {
+            make_writable(Path::new(&part)).map_err(|e| ApiError::from(e).at(target))?;
+        }
+        rename(&part, target).map_err(|e| ApiError::from(e).at(target))?;
+        Ok(true)
+    }
+
+    /// add a downloaded file, that already passed its hash check, to the store
+    pub fn insert(&self, sha512: &str, file: &str) -> Result<(), ApiError> {
+        let target = match self.path(sha512) {
+            Some(path) if !path.is_file() => path,
+            _ => return Ok(()),
+        };
+        if let Some(dir) = target.parent() {
+            create_dir_all(dir).map_err(|e| ApiError::from(e).at(dir.display()))?;
+        }
+        let part = PathBuf::from(target.to_string_lossy().to_string() + PART_SUFFIX);
+        link(Path::new(file), &part, self.mode).map_err(|e| e.at(file))?;
+        // a hardlinked store file is the installed file, it has to stay writable like any other mod
+        if self.mode != LinkMode::HARDLINK {
+            let mut permissions = part
+                .metadata()
+                .map_err(|e| ApiError::from(e).at(part.display()))?
+                .permissions();
+            permissions.set_readonly(true);
+            std::fs::set_permissions(&part, permissions)
+                .map_err(|e| ApiError::from(e).at(part.display()))?;
+        }
+        rename(&part, &target).map_err(|e| ApiError::from(e).at(target.display()))
+    }
+
+    /// Remove every stored file whose hash is not in `keep`.
+    ///
+    /// Returns the number of removed files and the bytes they took.
+    pub fn gc(&self, keep: &HashSet<String>) -> Result<(usize, u64), ApiError> {
+        let mut removed = 0;
+        let mut freed = 0;
+        let dirs = match read_dir(&self.dir) {
+            Ok(dirs) => dirs,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(e) => return Err(ApiError::from(e).at(self.dir.display())),
+        };
+        for dir in dirs {
+            let dir = dir
+                .map_err(|e| ApiError::from(e).at(self.dir.display()))?
+                .path();
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in read_dir(&dir).map_err(|e| ApiError::from(e).at(dir.display()))? {
+                let path = entry
+                    .map_err(|e| ApiError::from(e).at(dir.display()))?
+                    .path();
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if keep.contains(name.as_ref()) {
+                    continue;
+                }
+                freed += path.metadata().map(|m| m.len()).unwrap_or(0);
+                remove_file(&path).map_err(|e| ApiError::from(e).at(path.display()))?;
+                removed += 1;
+            }
+            // only succeeds if the folder is empty now
+            let _ = remove_dir(&dir);
+        }
+        Ok((removed, freed))
+    }
+}
+
+/// true if the file has the given sha512 hash and size, a size of 0 is not checked
+fn is_intact(path: &Path, sha512: &str, size: u64) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return false;
+    };
+    if size != 0 && file.metadata().map(|m| m.len()).ok() != Some(size) {
+        return false;
+    }
+    let mut hasher = Sha512::new();
+    io::copy(&mut file, &mut hasher).is_ok()
+        && base16ct::lower::encode_string(&hasher.finalize()).eq_ignore_ascii_case(sha512)
+}
+
+/// give the owner of the file write access
+fn make_writable(path: &Path) -> io::Result<()> {
+    let mut permissions = path.metadata()?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(path, permissions)
+}
+
+/// put `source` at `target`, the way `mode` says
+fn link(source: &Path, target: &Path, mode: LinkMode) -> Result<(), ApiError> {
+    let result = match mode {
+        LinkMode::HARDLINK => {
+            hard_link(source, target).or_else(|_| copy(source, target).map(|_| ()))
+        }
+        LinkMode::REFLINK => reflink_copy::reflink_or_copy(source, target).map(|_| ()),
+        LinkMode::COPY => copy(source, target).map(|_| ()),
+    };
+    result.map_err(ApiError::from)
+}
+
+/// Remove the files from the store that no pack in the pack path installs.
+///
+/// Returns the number of removed files and the bytes they took.
+pub fn collect_garbage(config: &Configuration) -> Result<(usize, u64), ApiError> {
+    let mut keep = HashSet::new();
+    for pack in Pack::open_all(config)? {
+        keep.extend(pack.file_hashes()?);
+    }
+    JarStore::new(&config.store_path, config.link_mode).gc(&keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::TempDir;
+
+    /// a fresh store in the temp dir, with a file holding `content` to put into it
+    fn store(name: &str, content: &[u8]) -> (JarStore, TempDir, String) {
+        let dir = TempDir::new(&format!("store-test-{name}"));
+        let file = dir.join("mod.jar");
+        std::fs::write(&file, content).unwrap();
+        let hash = base16ct::lower::encode_string(&Sha512::digest(content));
+        let store = JarStore::new(&dir.join("store").to_string_lossy(), LinkMode::COPY);
+        (store, dir, hash)
+    }
+
+    #[test]
+    fn path_fans_out_by_the_first_two_characters() {
+        let store = JarStore::new("/store", LinkMode::COPY);
+        assert_eq!(store.path("ab12"), Some(PathBuf::from("/store/ab/ab12")));
+        assert_eq!(store.path("AB12"), Some(PathBuf::from("/store/AB/AB12")));
+    }
+
+    #[test]
+    fn path_rejects_anything_but_hex() {
+        let store = JarStore::new("/store", LinkMode::COPY);
+        assert_eq!(store.path(""), None);
+        assert_eq!(store.path("a"), None);
+        assert_eq!(store.path("../../etc/passwd"), None);
+        assert_eq!(store.path("ab/cd"), None);
+        assert_eq!(store.path("abcg"), None);
+        assert!(!store.contains("../mod.jar"));
+    }
+
+    #[test]
+    fn insert_and_place_a_file() {
+        let (store, dir, hash) = store("place", b"mod content");
+        let file = dir.join("mod.jar").to_string_lossy().to_string();
+        assert!(!store.contains(&hash));
+        store.insert(&hash, &file).unwrap();
+        assert!(store.contains(&hash));
+
+        let target = dir.join("installed.jar").to_string_lossy().to_string();
+        assert!(store.place(&hash, 11, &target).unwrap());
+        assert_eq!(std::fs::read(&target).unwrap(), b"mod content");
+        // the store file stays read only, the installed copy does not
+        assert!(store
+            .path(&hash)
+            .unwrap()
+            .metadata()
+            .unwrap()
+            .permissions()
+            .readonly());
+        assert!(!Path::new(&target)
+            .metadata()
+            .unwrap()
+            .permissions()
+            .readonly());
+        assert!(!Path::new(&(target + PART_SUFFIX)).exists());
+    }
+
+    #[test]
+    fn place_ignores_invalid_and_missing_hashes() {
+        let (store, dir, hash) = store("invalid", b"mod content");
+        let target = dir.join("installed.jar").to_string_lossy().to_string();
+        assert!(!store.place("../mod.jar", 0, &target).unwrap());
+        assert!(!store.place(&hash, 0, &target).unwrap());
+        assert!(!Path::new(&target).exists());
+    }
+
+    #[test]
+    fn place_removes_a_corrupt_file() {
+        let (store, dir, hash) = store("corrupt", b"mod content");
+        let file = dir.join("mod.jar").to_string_lossy().to_string();
+        store.insert(&hash, &file).unwrap();
+        let target = dir.join("installed.jar").to_string_lossy().to_string();
+
+        // a wrong size is caught before hashing
+        assert!(!store.place(&hash, 12, &target).unwrap());
+        assert!(!store.contains(&hash));
+
+        store.insert(&hash, &file).unwrap();
+        let stored = store.path(&hash).unwrap();
+        let mut permissions = stored.metadata().unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&stored, permissions).unwrap();
+        std::fs::write(&stored, b"mod c0ntent").unwrap();
+        assert!(!store.place(&hash, 11, &target).unwrap());
+        assert!(!store.contains(&hash));
+        assert!(!Path::new(&target).exists());
+    }
+
+    #[test]
+    fn gc_keeps_only_the_given_hashes() {
+        let (store, dir, hash) = store("gc", b"mod content");
+        let file = dir.join("mod.jar");
+        store.insert(&hash, &file.to_string_lossy()).unwrap();
+        std::fs::write(&file, b"other mod").unwrap();
+        let other = base16ct::lower::encode_string(&Sha512::digest(b"other mod"));
+        store.insert(&other, &file.to_string_lossy()).unwrap();
+
+        assert_eq!(store.gc(&HashSet::from([hash.clone()])).unwrap(), (1, 9));
+        assert!(store.contains(&hash));
+        assert!(!store.contains(&other));
+    }
+}
@@ -1,48 +1,279 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, io};
+
+use reqwest::StatusCode;
 
 #[derive(Debug)]
-enum ApiErrorKind {
+pub enum ApiErrorKind {
+    /// the project or version does not exist, or the token cannot see it
     NotFound,
-    InvalidData,
+    /// the project exists, but no version matches the requested game version and loader
+    NoCompatibleVersion {
+        project: String,
+    },
+    /// the server answered with an unsuccessful status
+    Http {
+        status: StatusCode,
+        body: String,
+    },
+    /// the server could not be reached, or the connection broke
+    Transport(reqwest::Error),
+    /// a response could not be parsed, `path` points to the offending field
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+    Io(io::Error),
+    /// the request is not cached and cannot be sent in offline mode
+    Offline,
+    /// a downloaded file does not match its hash, `quarantined` is where it was moved to
+    HashMismatch {
+        expected: String,
+        actual: String,
+        quarantined: Option<String>,
+    },
+    /// a downloaded file does not have the expected size
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+        quarantined: Option<String>,
+    },
+    InvalidData(String),
 }
 
+/// Error of any interaction with the api, a pack or the file system.
+///
+/// Besides its kind it carries the url or file path the error occurred at.
 #[derive(Debug)]
 pub struct ApiError {
     kind: ApiErrorKind,
+    location: Option<String>,
 }
 
 impl Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let to_display = match self.kind {
-            ApiErrorKind::NotFound => "NotFound",
-            ApiErrorKind::InvalidData => "InvalidData",
-        };
-        write!(f, "{}", to_display)
+        match &self.kind {
+            ApiErrorKind::NotFound => write!(f, "Not found"),
+            ApiErrorKind::NoCompatibleVersion { project } => {
+                write!(f, "No compatible version of '{project}'")
+            }
+            ApiErrorKind::Http { status, body } if body.is_empty() => {
+                write!(f, "Server answered with {status}")
+            }
+            ApiErrorKind::Http { status, body } => {
+                write!(f, "Server answered with {status}: {body}")
+            }
+            ApiErrorKind::Transport(e) => {
+                write!(f, "Network error: {e}")?;
+                // reqwest hides the actual cause, e.g. a refused connection, in its sources
+                let mut source = e.source();
+                while let Some(cause) = source {
+                    write!(f, ": {cause}")?;
+                    source = cause.source();
+                }
+                Ok(())
+            }
+            ApiErrorKind::Json { path, source } => {
+                write!(f, "Invalid response at '{path}': {source}")
+            }
+            ApiErrorKind::Io(e) => write!(f, "{e}"),
+            ApiErrorKind::Offline => write!(f, "Not cached, cannot be requested offline"),
+            ApiErrorKind::HashMismatch {
+                expected,
+                actual,
+                quarantined,
+            } => {
+                write!(
+                    f,
+                    "Downloaded data does not match its hash, expected {expected}, got {actual}"
+                )?;
+                write_quarantined(f, quarantined)
+            }
+            ApiErrorKind::SizeMismatch {
+                expected,
+                actual,
+                quarantined,
+            } => {
+                write!(
+                    f,
+                    "Downloaded data has {actual} bytes instead of {expected}"
+                )?;
+                write_quarantined(f, quarantined)
+            }
+            ApiErrorKind::InvalidData(msg) => write!(f, "{msg}"),
+        }?;
+        match &self.location {
+            Some(location) => write!(f, " ({location})"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn write_quarantined(f: &mut std::fmt::Formatter<'_>, quarantined: &Option<String>) -> std::fmt::Result {
+    match quarantined {
+        Some(path) => write!(f, ", moved it to '{path}'"),
+        None => Ok(()),
     }
 }
 
 impl Error for ApiError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+        match &self.kind {
+            ApiErrorKind::Transport(e) => Some(e),
+            ApiErrorKind::Json { source, .. } => Some(source),
+            ApiErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ApiErrorKind> for ApiError {
+    fn from(kind: ApiErrorKind) -> Self {
+        ApiError {
+            kind,
+            location: None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        let location = e.url().map(|url| url.to_string());
+        let kind = match e.status() {
+            Some(status) => ApiErrorKind::Http {
+                status,
+                body: String::new(),
+            },
+            None => ApiErrorKind::Transport(e.without_url()),
+        };
+        ApiError { kind, location }
     }
+}
 
-    fn description(&self) -> &str {
-        "description() is deprecated; use Display"
+impl From<io::Error> for ApiError {
+    fn from(e: io::Error) -> Self {
+        ApiErrorKind::Io(e).into()
     }
+}
 
-    fn cause(&self) -> Option<&dyn Error> {
-        self.source()
+impl From<toml::de::Error> for ApiError {
+    fn from(e: toml::de::Error) -> Self {
+        ApiErrorKind::InvalidData(e.to_string()).into()
     }
+}
 
-    //fn provide<'a>(&'a self, request: &mut std::error::Request<'a>) {}
+impl From<toml::ser::Error> for ApiError {
+    fn from(e: toml::ser::Error) -> Self {
+        ApiErrorKind::InvalidData(e.to_string()).into()
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiErrorKind::Json {
+            path: ".".to_string(),
+            source: e,
+        }
+        .into()
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for ApiError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        ApiErrorKind::Json {
+            path: e.path().to_string(),
+            source: e.into_inner(),
+        }
+        .into()
+    }
+}
+
+impl From<zip::result::ZipError> for ApiError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => e.into(),
+            e => ApiErrorKind::InvalidData(e.to_string()).into(),
+        }
+    }
 }
 
 impl ApiError {
     pub fn not_found() -> Self {
-        ApiError { kind: ApiErrorKind::NotFound }
+        ApiErrorKind::NotFound.into()
+    }
+
+    pub fn invalid_data(msg: impl Into<String>) -> Self {
+        ApiErrorKind::InvalidData(msg.into()).into()
+    }
+
+    pub fn no_compatible_version(project: &str) -> Self {
+        ApiErrorKind::NoCompatibleVersion {
+            project: project.to_string(),
+        }
+        .into()
+    }
+
+    /// an unsuccessful response, 404 is reported as not found
+    pub fn http(status: StatusCode, body: String) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => ApiErrorKind::NotFound.into(),
+            status => ApiErrorKind::Http { status, body }.into(),
+        }
+    }
+
+    /// set the url or file path the error occurred at
+    pub fn at(mut self, location: impl Display) -> Self {
+        self.location = Some(location.to_string());
+        self
+    }
+
+    pub fn kind(&self) -> &ApiErrorKind {
+        &self.kind
+    }
+
+    /// the url or file path the error occurred at
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// the http status of the response, if the server answered at all
+    pub fn status(&self) -> Option<StatusCode> {
+        match &self.kind {
+            ApiErrorKind::NotFound => Some(StatusCode::NOT_FOUND),
+            ApiErrorKind::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// true if the project or a compatible version of it does not exist
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self.kind,
+            ApiErrorKind::NotFound | ApiErrorKind::NoCompatibleVersion { .. }
+        )
+    }
+
+    /// true if the api could not be reached, either because of the network or offline mode
+    pub fn is_network(&self) -> bool {
+        matches!(
+            self.kind,
+            ApiErrorKind::Transport(_) | ApiErrorKind::Offline
+        )
     }
 
-    pub fn invalid_data() -> Self {
-        ApiError { kind: ApiErrorKind::InvalidData }
+    /// Exit code for the command line, so scripts can tell failures apart.
+    ///
+    /// 3: not found / no compatible version, 4: network or offline,
+    /// 5: unsuccessful http status, 6: invalid data, 7: file system
+    pub fn exit_code(&self) -> i32 {
+        match self.kind {
+            ApiErrorKind::NotFound | ApiErrorKind::NoCompatibleVersion { .. } => 3,
+            ApiErrorKind::Transport(_) | ApiErrorKind::Offline => 4,
+            ApiErrorKind::Http { .. } => 5,
+            ApiErrorKind::Json { .. }
+            | ApiErrorKind::InvalidData(_)
+            | ApiErrorKind::HashMismatch { .. }
+            | ApiErrorKind::SizeMismatch { .. } => 6,
+            ApiErrorKind::Io(_) => 7,
+        }
     }
 }
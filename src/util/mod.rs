@@ -1 +1,5 @@
 pub mod error;
+pub mod progress;
+pub mod output;
+#[cfg(test)]
+pub mod testing;
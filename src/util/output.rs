@@ -0,0 +1,26 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Send prompts and progress messages to stderr instead of stdout.
+///
+/// Used by `--dry-run`, so stdout only carries the plan.
+pub fn messages_to_stderr(enabled: bool) {
+    TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_to_stderr() -> bool {
+    TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// `println!` for prompts and progress messages, see [`messages_to_stderr`]
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::util::output::is_to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,218 @@
+use std::{
+    io::{stdout, IsTerminal, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// how often the progress line is redrawn at most
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 20;
+
+/// Progress of a batch of downloads, drawn as a single line on stdout.
+///
+/// Several files may be downloaded at the same time, each one reports
+/// through the [`FileProgress`] returned by [`Progress::start_file`].
+/// Nothing is drawn when stdout is not a terminal, so piped output stays clean.
+#[derive(Debug)]
+pub struct Progress {
+    enabled: bool,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Debug)]
+struct ProgressState {
+    total: u64,
+    done: u64,
+    file_count: usize,
+    files_done: usize,
+    active: Vec<ActiveFile>,
+    next_id: usize,
+    last_draw: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct ActiveFile {
+    id: usize,
+    name: String,
+    size: u64,
+    done: u64,
+}
+
+/// The progress of a single file, it is finished when dropped.
+#[derive(Debug)]
+pub struct FileProgress<'a> {
+    progress: &'a Progress,
+    id: usize,
+}
+
+impl Progress {
+    /// progress for `file_count` downloads with a combined size of `total` bytes, 0 if unknown
+    pub fn new(total: u64, file_count: usize) -> Self {
+        Progress {
+            enabled: stdout().is_terminal(),
+            state: Mutex::new(ProgressState {
+                total,
+                done: 0,
+                file_count,
+                files_done: 0,
+                active: Vec::new(),
+                next_id: 0,
+                last_draw: None,
+            }),
+        }
+    }
+
+    /// progress that is never drawn
+    pub fn hidden() -> Self {
+        Progress {
+            enabled: false,
+            ..Progress::new(0, 0)
+        }
+    }
+
+    /// start a file of `size` bytes, 0 if unknown
+    pub fn start_file(&self, name: &str, size: u64) -> FileProgress<'_> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.active.push(ActiveFile {
+            id,
+            name: name.to_string(),
+            size,
+            done: 0,
+        });
+        state.last_draw = None;
+        self.draw(&mut state);
+        FileProgress { progress: self, id }
+    }
+
+    /// print a line above the progress line
+    pub fn println(&self, line: &str) {
+        let mut state = self.state.lock().unwrap();
+        self.clear();
+        println!("{line}");
+        state.last_draw = None;
+        if !state.active.is_empty() {
+            self.draw(&mut state);
+        }
+    }
+
+    fn advance(&self, id: usize, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(file) = state.active.iter_mut().find(|f| f.id == id) {
+            file.done += bytes;
+        }
+        state.done += bytes;
+        self.draw(&mut state);
+    }
+
+    fn restart_file(&self, id: usize, done: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(file) = state.active.iter_mut().find(|f| f.id == id) {
+            let before = std::mem::replace(&mut file.done, done);
+            state.done = state.done - before + done;
+        }
+        self.draw(&mut state);
+    }
+
+    /// Bytes that were not downloaded, e.g. because the download failed,
+    /// still count as done, so the total keeps adding up.
+    fn finish_file(&self, id: usize) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(i) = state.active.iter().position(|f| f.id == id) {
+            let file = state.active.remove(i);
+            state.done += file.size.saturating_sub(file.done);
+        }
+        state.files_done += 1;
+        self.clear();
+        if !state.active.is_empty() {
+            state.last_draw = None;
+            self.draw(&mut state);
+        }
+    }
+
+    fn draw(&self, state: &mut ProgressState) {
+        if !self.enabled
+            || state
+                .last_draw
+                .is_some_and(|t| t.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        state.last_draw = Some(Instant::now());
+        let line = match state.active.as_slice() {
+            // a single download shows the bar of its file
+            [file] if state.file_count <= 1 => format!(
+                "{} {} {}",
+                file.name,
+                bar(file.done, file.size),
+                mib(file.done, file.size)
+            ),
+            active => format!(
+                "{} {} | {}/{} files | {}",
+                bar(state.done, state.total),
+                mib(state.done, state.total),
+                state.files_done,
+                state.file_count,
+                active
+                    .iter()
+                    .map(|file| match file.size {
+                        0 => file.name.clone(),
+                        size => format!("{} {}%", file.name, file.done.min(size) * 100 / size),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+        print!("\r\x1b[2K{line}");
+        let _ = stdout().flush();
+    }
+
+    fn clear(&self) {
+        if self.enabled {
+            print!("\r\x1b[2K");
+            let _ = stdout().flush();
+        }
+    }
+}
+
+impl FileProgress<'_> {
+    /// count `bytes` more bytes of the file as done
+    pub fn advance(&self, bytes: u64) {
+        self.progress.advance(self.id, bytes);
+    }
+
+    /// start counting the file again at `done` bytes, e.g. when a download is resumed
+    pub fn restart_at(&self, done: u64) {
+        self.progress.restart_file(self.id, done);
+    }
+}
+
+impl Drop for FileProgress<'_> {
+    fn drop(&mut self) {
+        self.progress.finish_file(self.id);
+    }
+}
+
+/// a bar like `[#####     ]  50%`, empty if the size is unknown
+fn bar(done: u64, size: u64) -> String {
+    if size == 0 {
+        return String::new();
+    }
+    let filled = (done.min(size) as f64 / size as f64 * BAR_WIDTH as f64) as usize;
+    format!(
+        "[{}{}] {:>3}%",
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled),
+        done.min(size) * 100 / size
+    )
+}
+
+/// `done/size MiB`, only `done` if the size is unknown
+fn mib(done: u64, size: u64) -> String {
+    let to_mib = |bytes: u64| bytes as f64 / 1048576_f64;
+    match size {
+        0 => format!("{:.2} MiB", to_mib(done)),
+        _ => format!("{:.2}/{:.2} MiB", to_mib(done), to_mib(size)),
+    }
+}
@@ -0,0 +1,185 @@
+// Helpers for tests: a mock http server and temporary folders.
+//
+// Only std and serde_json are used, so the binary can include this file
+// for its own tests as well.
+
+use std::{
+    fs::{create_dir_all, remove_dir_all},
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver},
+    },
+    thread,
+};
+
+use serde_json::Value;
+
+/// A request the mock server got.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// path and query, e.g. `/projects?ids=...`
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// the value of a header, the name is not case sensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A response of the mock server.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// only this many bytes of the body are sent before the connection is closed
+    pub cut_off: Option<usize>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            cut_off: None,
+        }
+    }
+
+    pub fn json(value: Value) -> Self {
+        Response::new(200, value.to_string()).header("Content-Type", "application/json")
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// close the connection after `len` bytes of the body, as if it broke
+    pub fn cut_off(mut self, len: usize) -> Self {
+        self.cut_off = Some(len);
+        self
+    }
+}
+
+/// Answer `count` requests in a thread, with what `respond` returns for them.
+///
+/// Returns the url of the server and the requests it got. Further requests
+/// are refused, so a test fails if more requests are made than expected.
+pub fn serve(
+    count: usize,
+    respond: impl Fn(&Request) -> Response + Send + 'static,
+) -> (String, Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().take(count) {
+            let mut stream = stream.unwrap();
+            let request = read_request(&mut BufReader::new(stream.try_clone().unwrap()));
+            let response = respond(&request);
+            let _ = sender.send(request);
+            let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+            for (name, value) in &response.headers {
+                head += &format!("{name}: {value}\r\n");
+            }
+            head += &format!(
+                "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                response.body.len()
+            );
+            let sent = response.cut_off.unwrap_or(response.body.len());
+            // the client may hang up early, e.g. on an error status
+            let _ = stream
+                .write_all(head.as_bytes())
+                .and_then(|_| stream.write_all(&response.body[..sent]));
+        }
+    });
+    (url, receiver)
+}
+
+/// the url of a server that answers every request with the json `respond` returns for its path
+pub fn serve_json(count: usize, respond: fn(&str) -> Value) -> String {
+    serve(count, move |request| Response::json(respond(&request.path))).0
+}
+
+fn read_request(reader: &mut impl BufRead) -> Request {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split(' ');
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: String::new(),
+    };
+    let length = request
+        .header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    request.body = String::from_utf8_lossy(&body).to_string();
+    request
+}
+
+/// A folder in the temp dir that is only used by one test, it is removed when dropped.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "mapito-{name}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    /// the path as string, like the config stores it
+    pub fn path_str(&self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}